@@ -0,0 +1,337 @@
+use std::fs;
+use std::path::Path;
+
+use base64::Engine;
+use imagesize::ImageType;
+
+use crate::config::{AttachmentsConfig, RunConfig};
+use crate::models::{Attachment, AttachmentKind, Chat, ImageInfo};
+use crate::run;
+
+/// Bytes looked at to tell binary files apart
+const SNIFF_SIZE: usize = 8 * 1024;
+
+/// `@path` words of `text`, with trailing punctuation dropped unless it is
+/// part of an existing path. Mentions of missing paths and of directories
+/// are plain text.
+pub fn mentions(text: &str) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+
+    for word in text.split_whitespace() {
+        let Some(path) = word.strip_prefix('@').filter(|p| !p.is_empty()) else {
+            continue;
+        };
+
+        let trimmed = path.trim_end_matches(['.', ',', ':', ';', ')', '?', '!', '\'', '"']);
+
+        let path = [path, trimmed]
+            .into_iter()
+            .find(|p| !p.is_empty() && Path::new(p).is_file());
+
+        if let Some(path) = path.filter(|p| !paths.iter().any(|known| known == p)) {
+            paths.push(path.to_string());
+        }
+    }
+
+    paths
+}
+
+/// Reads every file mentioned in `text`. Images have their own size limit,
+/// the total only counts text.
+pub fn resolve(text: &str, config: &AttachmentsConfig) -> anyhow::Result<Vec<Attachment>> {
+    let mut attachments: Vec<Attachment> = Vec::new();
+    let mut total = 0;
+
+    for name in mentions(text) {
+        if audio_mime(&name).is_some() {
+            anyhow::bail!("@{} is audio, use /transcribe @{}", name, name);
+        }
+
+        if is_image(&name) {
+            let image =
+                read_image(&name, config.max_image_size).map_err(|e| anyhow::anyhow!("@{}", e))?;
+            attachments.push(image);
+            continue;
+        }
+
+        let content =
+            read_file(&name, config.max_file_size).map_err(|e| anyhow::anyhow!("@{}", e))?;
+
+        total += content.len();
+
+        if total > config.max_total_size {
+            anyhow::bail!(
+                "attachments are over {} in total",
+                human_size(config.max_total_size)
+            );
+        }
+
+        attachments.push(Attachment {
+            kind: AttachmentKind::File,
+            path: fs::canonicalize(&name).ok(),
+            name,
+            size: content.len(),
+            checksum: Some(checksum(&content)),
+            content,
+            image: None,
+            changed: false,
+        });
+    }
+
+    Ok(attachments)
+}
+
+/// Loads the attachments of `chat` that were unloaded when it was saved
+pub fn load_all(chat: &mut Chat, config: &AttachmentsConfig) {
+    chat.messages
+        .items
+        .iter_mut()
+        .flat_map(|m| m.attachments.iter_mut())
+        .for_each(|a| load(a, config));
+}
+
+/// Reads an unloaded attachment again. A file that cannot be read is sent as
+/// a note saying so, the chat must stay usable. An image that cannot be read
+/// is left out of the request. Edits since it was attached mark it `changed`.
+pub fn load(attachment: &mut Attachment, config: &AttachmentsConfig) {
+    let Some(path) = attachment.path.clone().filter(|_| !attachment.is_loaded()) else {
+        return;
+    };
+    let name = path.to_string_lossy();
+
+    let read = match attachment.kind {
+        AttachmentKind::Image => read_image(&name, config.max_image_size).map(|image| {
+            attachment.image = image.image;
+            image.content
+        }),
+        _ => read_file(&name, config.max_file_size),
+    };
+
+    match read {
+        Ok(content) => {
+            attachment.changed = attachment
+                .checksum
+                .is_some_and(|sum| sum != checksum(&content));
+            attachment.content = content;
+        }
+        Err(_) if attachment.kind == AttachmentKind::Image => {}
+        Err(e) => attachment.content = format!("[could not be read again: {}]", e),
+    }
+}
+
+/// FNV-1a, stable across builds unlike the std hasher
+pub fn checksum(content: &str) -> u64 {
+    content.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Reads a text file of at most `max_size` bytes
+pub fn read_file(name: &str, max_size: usize) -> anyhow::Result<String> {
+    let path = Path::new(name);
+
+    if path.is_dir() {
+        anyhow::bail!("{} is a directory", name);
+    }
+
+    let size = fs::metadata(path)
+        .map_err(|e| anyhow::anyhow!("{}: {}", name, e))?
+        .len() as usize;
+
+    if size > max_size {
+        anyhow::bail!(
+            "{} is {}, the limit is {}",
+            name,
+            human_size(size),
+            human_size(max_size)
+        );
+    }
+
+    let bytes = fs::read(path).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+
+    if is_binary(&bytes) {
+        anyhow::bail!("{} looks like a binary file", name);
+    }
+
+    String::from_utf8(bytes).map_err(|_| anyhow::anyhow!("{} is not UTF-8", name))
+}
+
+/// PNG and JPEG files, by extension
+pub fn is_image(name: &str) -> bool {
+    let extension = Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase());
+
+    matches!(extension.as_deref(), Some("png" | "jpg" | "jpeg"))
+}
+
+/// Type of WAV, MP3 and M4A files, by extension
+pub fn audio_mime(name: &str) -> Option<&'static str> {
+    let extension = Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase());
+
+    match extension.as_deref() {
+        Some("wav") => Some("audio/wav"),
+        Some("mp3") => Some("audio/mpeg"),
+        Some("m4a") => Some("audio/mp4"),
+        _ => None,
+    }
+}
+
+/// Reads a PNG or JPEG of at most `max_size` bytes, base64 encoded
+pub fn read_image(name: &str, max_size: usize) -> anyhow::Result<Attachment> {
+    let size = fs::metadata(name)
+        .map_err(|e| anyhow::anyhow!("{}: {}", name, e))?
+        .len() as usize;
+
+    if size > max_size {
+        anyhow::bail!(
+            "{} is {}, the limit for images is {}",
+            name,
+            human_size(size),
+            human_size(max_size)
+        );
+    }
+
+    let bytes = fs::read(name).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+
+    let mime = match imagesize::image_type(&bytes) {
+        Ok(ImageType::Png) => "image/png",
+        Ok(ImageType::Jpeg) => "image/jpeg",
+        _ => anyhow::bail!("{} is not a PNG or JPEG image", name),
+    };
+
+    let dimensions =
+        imagesize::blob_size(&bytes).map_err(|e| anyhow::anyhow!("{}: {:?}", name, e))?;
+    let content = base64::engine::general_purpose::STANDARD.encode(&bytes);
+
+    Ok(Attachment {
+        kind: AttachmentKind::Image,
+        name: name.to_string(),
+        path: fs::canonicalize(name).ok(),
+        checksum: Some(checksum(&content)),
+        content,
+        size,
+        image: Some(ImageInfo {
+            mime: mime.to_string(),
+            width: dimensions.width,
+            height: dimensions.height,
+            size,
+        }),
+        changed: false,
+    })
+}
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(SNIFF_SIZE)].contains(&0)
+}
+
+/// The command of an input starting with `!`
+pub fn command(text: &str) -> Option<&str> {
+    text.strip_prefix('!')
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+}
+
+/// Runs `command` and keeps the end of its output, where errors usually are
+pub async fn command_output(command: &str, config: &RunConfig) -> anyhow::Result<Attachment> {
+    let (output, status) = run::capture(command, config).await?;
+
+    let mut content = match output.len() > config.max_output {
+        true => {
+            let mut start = output.len() - config.max_output;
+            while !output.is_char_boundary(start) {
+                start += 1;
+            }
+
+            format!("[{} truncated]\n{}", human_size(start), &output[start..])
+        }
+        false => output,
+    };
+
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&format!("[{}]", status));
+
+    Ok(Attachment {
+        kind: AttachmentKind::Command,
+        name: command.to_string(),
+        path: None,
+        size: content.len(),
+        content,
+        image: None,
+        checksum: None,
+        changed: false,
+    })
+}
+
+/// `2048` -> `2.0 KB`
+pub fn human_size(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+/// Completion of a `@path` at the end of the input, relative to the current
+/// directory: the text to append and the matching entries
+pub fn completion(input: &str) -> Option<(String, Vec<String>)> {
+    let word = input.rsplit(char::is_whitespace).next()?;
+    let partial = word.strip_prefix('@')?;
+
+    let (dir, prefix) = match partial.rsplit_once('/') {
+        Some((dir, prefix)) => (format!("{}/", dir), prefix),
+        None => (String::new(), partial),
+    };
+
+    let read_dir = match dir.is_empty() {
+        true => fs::read_dir("."),
+        false => fs::read_dir(&dir),
+    };
+
+    let mut entries: Vec<String> = read_dir
+        .ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+
+            // hidden files only when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+
+            match entry.file_type().ok()?.is_dir() {
+                true => Some(format!("{}/", name)),
+                false => Some(name),
+            }
+        })
+        .collect();
+
+    entries.sort();
+
+    let common = entries
+        .iter()
+        .skip(1)
+        .fold(entries.first()?.clone(), |common, entry| {
+            common
+                .chars()
+                .zip(entry.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect()
+        });
+
+    let mut suffix = common[prefix.len()..].to_string();
+
+    // a complete file name is followed by a space, directories go on
+    if let [entry] = entries.as_slice() {
+        if !entry.ends_with('/') {
+            suffix.push(' ');
+        }
+    }
+
+    Some((suffix, entries))
+}
@@ -0,0 +1,181 @@
+use std::io::{self, IsTerminal, Read, Write};
+
+use crate::attach;
+use crate::config::{self, Config};
+use crate::models::{Chat, Message, Role, TitleSource};
+use crate::openai::stream_message;
+use crate::storage;
+
+const USAGE: &str = "Usage:
+  chatgpt-tui                        start the interactive UI
+  chatgpt-tui ask [OPTIONS] PROMPT   send a single prompt and print the answer
+
+Options for `ask`:
+  -c, --chat NAME    append the exchange to the saved chat NAME (created if missing)
+  -m, --model MODEL  model to use instead of the configured one
+  -h, --help         print this help
+  --                 end of options, the rest is the prompt
+
+When stdin is piped it is appended to the prompt, e.g.
+  git diff | chatgpt-tui ask \"review this\"";
+
+#[derive(Debug, Default)]
+pub struct AskArgs {
+    pub prompt: String,
+    pub chat: Option<String>,
+    pub model: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Help,
+    Ask(AskArgs),
+}
+
+/// Returns `None` when the interactive UI should be started
+pub fn parse(args: impl Iterator<Item = String>) -> anyhow::Result<Option<Command>> {
+    let mut args = args.skip(1);
+
+    let Some(command) = args.next() else {
+        return Ok(None);
+    };
+
+    match command.as_str() {
+        "-h" | "--help" | "help" => Ok(Some(Command::Help)),
+        "ask" => {
+            let mut ask = AskArgs::default();
+            let mut prompt: Vec<String> = Vec::new();
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    // the rest is the prompt, even if it looks like an option
+                    "--" => prompt.extend(args.by_ref()),
+                    "-c" | "--chat" => {
+                        ask.chat = Some(args.next().ok_or(anyhow::anyhow!("--chat needs a name"))?)
+                    }
+                    "-m" | "--model" => {
+                        ask.model = Some(
+                            args.next()
+                                .ok_or(anyhow::anyhow!("--model needs a value"))?,
+                        )
+                    }
+                    "-h" | "--help" => return Ok(Some(Command::Help)),
+                    _ => prompt.push(arg),
+                }
+            }
+
+            ask.prompt = prompt.join(" ");
+
+            Ok(Some(Command::Ask(ask)))
+        }
+        other => Err(anyhow::anyhow!("unknown command `{}`\n\n{}", other, USAGE)),
+    }
+}
+
+fn read_piped_stdin() -> anyhow::Result<Option<String>> {
+    let mut stdin = io::stdin();
+
+    if stdin.is_terminal() {
+        return Ok(None);
+    }
+
+    let mut input = String::new();
+    stdin.read_to_string(&mut input)?;
+
+    Ok(Some(input).filter(|i| !i.trim().is_empty()))
+}
+
+async fn ask(args: AskArgs, config: &Config) -> anyhow::Result<()> {
+    let content = match (args.prompt.trim(), read_piped_stdin()?) {
+        ("", None) => return Err(anyhow::anyhow!("nothing to ask\n\n{}", USAGE)),
+        ("", Some(stdin)) => stdin,
+        (prompt, None) => prompt.to_string(),
+        (prompt, Some(stdin)) => format!("{}\n\n{}", prompt, stdin),
+    };
+
+    if let (Some(_), Some(pid)) = (&args.chat, storage::locked_by()) {
+        anyhow::bail!(
+            "the chats are open in chatgpt-tui (pid {}), close it or leave out --chat",
+            pid
+        );
+    }
+
+    let mut state = match args.chat {
+        Some(_) => storage::load()?,
+        None => Default::default(),
+    };
+
+    let chat_idx = args.chat.as_deref().map(|title| {
+        let existing = state
+            .chats
+            .iter()
+            .position(|c| c.title.eq_ignore_ascii_case(title));
+
+        existing.unwrap_or_else(|| {
+            let mut chat = Chat::new(title);
+            chat.title_source = TitleSource::User;
+            state.chats.push(chat);
+            state.chats.len() - 1
+        })
+    });
+
+    let mut chat = match chat_idx {
+        Some(i) => state.chats[i].clone(),
+        None => Chat::new("ask"),
+    };
+
+    chat.append_message(Message::new(Role::User, &content));
+    attach::load_all(&mut chat, &config.attachments);
+
+    let model = args.model.unwrap_or(config.model.clone());
+    let mut stdout = io::stdout();
+
+    let answer = stream_message(chat.clone(), &model, |chunk| {
+        print!("{}", chunk);
+        let _ = stdout.flush();
+    })
+    .await?;
+
+    println!();
+
+    if chat_idx.is_some() {
+        chat.append_message(Message::assistant(&answer));
+        chat.unload_attachments();
+
+        // only this chat changed, anything saved meanwhile is kept
+        let mut state = storage::load()?;
+
+        match state.chats.iter_mut().find(|c| c.id == chat.id) {
+            Some(saved) => *saved = chat,
+            None => state.chats.push(chat),
+        }
+
+        storage::save(&state)?;
+    }
+
+    Ok(())
+}
+
+/// Runs a non-interactive command, exiting with a non-zero code on failure.
+/// The config is only loaded when needed, a broken one must not get in the
+/// way of `--help`.
+pub async fn run(command: Command) -> ! {
+    let res = match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Command::Ask(args) => match config::load() {
+            Ok(config) => ask(args, &config).await,
+            Err(e) => Err(e),
+        },
+    };
+
+    match res {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(libc::EXIT_FAILURE)
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+
+use crate::config::ClipboardConfig;
+
+/// Terminals and tmux drop larger OSC 52 payloads
+const OSC52_MAX_BYTES: usize = 100_000;
+/// The UI is blocked while the clipboard command runs
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Copies `text` and returns how it was done, for the notice. A configured
+/// command wins, OSC 52 gives no feedback on whether it worked.
+pub fn copy(text: &str, config: &ClipboardConfig) -> anyhow::Result<&'static str> {
+    if let Some(command) = &config.command {
+        run_command(command, text)?;
+        return Ok("clipboard command");
+    }
+
+    if !config.osc52 {
+        anyhow::bail!("no clipboard configured, set `clipboard.command` in the config");
+    }
+
+    if text.len() > OSC52_MAX_BYTES {
+        anyhow::bail!("too large for OSC 52, set `clipboard.command` in the config");
+    }
+
+    osc52(text)?;
+    Ok("terminal")
+}
+
+/// OSC 52 escape sequence, wrapped for tmux which otherwise swallows it
+fn osc52(text: &str) -> std::io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let sequence = format!("\x1b]52;c;{}\x07", encoded);
+
+    let sequence = match std::env::var_os("TMUX") {
+        Some(_) => format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b")),
+        None => sequence,
+    };
+
+    let mut stdout = std::io::stdout();
+    stdout.write_all(sequence.as_bytes())?;
+    stdout.flush()
+}
+
+fn run_command(command: &str, text: &str) -> anyhow::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("could not run `{}`: {}", command, e))?;
+
+    // a command that does not read its input would block the write
+    if let Some(mut stdin) = child.stdin.take() {
+        let text = text.to_string();
+        std::thread::spawn(move || stdin.write_all(text.as_bytes()));
+    }
+
+    let deadline = Instant::now() + COMMAND_TIMEOUT;
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!(
+                "`{}` did not finish in {}s",
+                command,
+                COMMAND_TIMEOUT.as_secs()
+            );
+        }
+
+        std::thread::sleep(Duration::from_millis(10));
+    };
+
+    if !status.success() {
+        anyhow::bail!("`{}` exited with {}", command, status);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,267 @@
+/// Every user facing action, listed in the command palette
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Command {
+    NewChat,
+    QuickNewChat,
+    RenameChat,
+    DeleteChat,
+    TagChat,
+    MoveChat,
+    FilterByTag,
+    TogglePin,
+    CycleSort,
+    ExportChat,
+    ExportAllChats,
+    ImportChats,
+    Find,
+    NextMatch,
+    PrevMatch,
+    DeleteMessage,
+    ScrollUp,
+    ScrollDown,
+    ScrollPageUp,
+    ScrollPageDown,
+    ScrollHalfPageUp,
+    ScrollHalfPageDown,
+    ScrollTop,
+    ScrollBottom,
+    ViewMessage,
+    ToggleWrap,
+    OpenInPager,
+    YankMessage,
+    YankCodeBlock,
+    YankQuote,
+    SaveCodeBlock,
+    ApplyPatch,
+    RunCodeBlock,
+    ToggleSidebar,
+    GrowSidebar,
+    ShrinkSidebar,
+    GrowInput,
+    ShrinkInput,
+    ToggleZoom,
+    FocusChats,
+    FocusMessages,
+    FocusInput,
+    SwitchModel,
+    CycleTheme,
+    OpenPalette,
+    Quit,
+}
+
+impl Command {
+    pub const ALL: [Command; 46] = [
+        Command::NewChat,
+        Command::QuickNewChat,
+        Command::RenameChat,
+        Command::DeleteChat,
+        Command::TagChat,
+        Command::MoveChat,
+        Command::FilterByTag,
+        Command::TogglePin,
+        Command::CycleSort,
+        Command::ExportChat,
+        Command::ExportAllChats,
+        Command::ImportChats,
+        Command::Find,
+        Command::NextMatch,
+        Command::PrevMatch,
+        Command::DeleteMessage,
+        Command::ScrollUp,
+        Command::ScrollDown,
+        Command::ScrollPageUp,
+        Command::ScrollPageDown,
+        Command::ScrollHalfPageUp,
+        Command::ScrollHalfPageDown,
+        Command::ScrollTop,
+        Command::ScrollBottom,
+        Command::ViewMessage,
+        Command::ToggleWrap,
+        Command::OpenInPager,
+        Command::YankMessage,
+        Command::YankCodeBlock,
+        Command::YankQuote,
+        Command::SaveCodeBlock,
+        Command::ApplyPatch,
+        Command::RunCodeBlock,
+        Command::ToggleSidebar,
+        Command::GrowSidebar,
+        Command::ShrinkSidebar,
+        Command::GrowInput,
+        Command::ShrinkInput,
+        Command::ToggleZoom,
+        Command::FocusChats,
+        Command::FocusMessages,
+        Command::FocusInput,
+        Command::SwitchModel,
+        Command::CycleTheme,
+        Command::OpenPalette,
+        Command::Quit,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Command::NewChat => "New chat",
+            Command::QuickNewChat => "Quick new chat",
+            Command::RenameChat => "Rename chat",
+            Command::DeleteChat => "Delete chat",
+            Command::TagChat => "Tag chat",
+            Command::MoveChat => "Move chat to folder",
+            Command::FilterByTag => "Filter chats by tag",
+            Command::TogglePin => "Pin/unpin chat",
+            Command::CycleSort => "Change chats sorting",
+            Command::ExportChat => "Export chat",
+            Command::ExportAllChats => "Export all chats",
+            Command::ImportChats => "Import ChatGPT export",
+            Command::Find => "Find in chat",
+            Command::NextMatch => "Next match",
+            Command::PrevMatch => "Previous match",
+            Command::DeleteMessage => "Delete message",
+            Command::ScrollUp => "Scroll messages up a line",
+            Command::ScrollDown => "Scroll messages down a line",
+            Command::ScrollPageUp => "Scroll messages up a page",
+            Command::ScrollPageDown => "Scroll messages down a page",
+            Command::ScrollHalfPageUp => "Scroll messages up half a page",
+            Command::ScrollHalfPageDown => "Scroll messages down half a page",
+            Command::ScrollTop => "Scroll to the first message",
+            Command::ScrollBottom => "Scroll to the last message",
+            Command::ViewMessage => "View message full screen",
+            Command::ToggleWrap => "Toggle soft wrap in the viewer",
+            Command::OpenInPager => "Open message in $PAGER",
+            Command::YankMessage => "Copy message",
+            Command::YankCodeBlock => "Copy code block",
+            Command::YankQuote => "Copy message as markdown quote",
+            Command::SaveCodeBlock => "Save code block to file",
+            Command::ApplyPatch => "Apply diff from message",
+            Command::RunCodeBlock => "Run shell code block",
+            Command::ToggleSidebar => "Show/hide chats sidebar",
+            Command::GrowSidebar => "Widen chats sidebar",
+            Command::ShrinkSidebar => "Narrow chats sidebar",
+            Command::GrowInput => "Grow input",
+            Command::ShrinkInput => "Shrink input",
+            Command::ToggleZoom => "Zoom focused pane",
+            Command::FocusChats => "Go to chats",
+            Command::FocusMessages => "Go to messages",
+            Command::FocusInput => "Go to input",
+            Command::SwitchModel => "Switch model",
+            Command::CycleTheme => "Switch theme",
+            Command::OpenPalette => "Command palette",
+            Command::Quit => "Quit",
+        }
+    }
+
+    /// Identifier used in the `[keys]` config tables
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::NewChat => "new_chat",
+            Command::QuickNewChat => "quick_new_chat",
+            Command::RenameChat => "rename_chat",
+            Command::DeleteChat => "delete_chat",
+            Command::TagChat => "tag_chat",
+            Command::MoveChat => "move_chat",
+            Command::FilterByTag => "filter_by_tag",
+            Command::TogglePin => "toggle_pin",
+            Command::CycleSort => "cycle_sort",
+            Command::ExportChat => "export_chat",
+            Command::ExportAllChats => "export_all_chats",
+            Command::ImportChats => "import_chats",
+            Command::Find => "find",
+            Command::NextMatch => "next_match",
+            Command::PrevMatch => "prev_match",
+            Command::DeleteMessage => "delete_message",
+            Command::ScrollUp => "scroll_up",
+            Command::ScrollDown => "scroll_down",
+            Command::ScrollPageUp => "scroll_page_up",
+            Command::ScrollPageDown => "scroll_page_down",
+            Command::ScrollHalfPageUp => "scroll_half_page_up",
+            Command::ScrollHalfPageDown => "scroll_half_page_down",
+            Command::ScrollTop => "scroll_top",
+            Command::ScrollBottom => "scroll_bottom",
+            Command::ViewMessage => "view_message",
+            Command::ToggleWrap => "toggle_wrap",
+            Command::OpenInPager => "open_in_pager",
+            Command::YankMessage => "yank_message",
+            Command::YankCodeBlock => "yank_code_block",
+            Command::YankQuote => "yank_quote",
+            Command::SaveCodeBlock => "save_code_block",
+            Command::ApplyPatch => "apply_patch",
+            Command::RunCodeBlock => "run_code_block",
+            Command::ToggleSidebar => "toggle_sidebar",
+            Command::GrowSidebar => "grow_sidebar",
+            Command::ShrinkSidebar => "shrink_sidebar",
+            Command::GrowInput => "grow_input",
+            Command::ShrinkInput => "shrink_input",
+            Command::ToggleZoom => "toggle_zoom",
+            Command::FocusChats => "focus_chats",
+            Command::FocusMessages => "focus_messages",
+            Command::FocusInput => "focus_input",
+            Command::SwitchModel => "switch_model",
+            Command::CycleTheme => "cycle_theme",
+            Command::OpenPalette => "open_palette",
+            Command::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Command::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Short description for the help bar
+    pub fn label(self) -> &'static str {
+        match self {
+            Command::NewChat => "new",
+            Command::QuickNewChat => "quick new",
+            Command::RenameChat => "rename",
+            Command::DeleteChat | Command::DeleteMessage => "delete",
+            Command::TagChat => "tag",
+            Command::MoveChat => "folder",
+            Command::FilterByTag => "filter",
+            Command::TogglePin => "pin",
+            Command::CycleSort => "sort",
+            Command::ExportChat => "export",
+            Command::ExportAllChats => "export all",
+            Command::ImportChats => "import",
+            Command::Find => "find",
+            Command::NextMatch => "next match",
+            Command::PrevMatch => "prev match",
+            Command::ScrollUp | Command::ScrollDown => "scroll",
+            Command::ScrollPageUp | Command::ScrollPageDown => "page",
+            Command::ScrollHalfPageUp | Command::ScrollHalfPageDown => "half page",
+            Command::ScrollTop => "top",
+            Command::ScrollBottom => "bottom",
+            Command::ViewMessage => "view",
+            Command::ToggleWrap => "wrap",
+            Command::OpenInPager => "pager",
+            Command::YankMessage => "copy",
+            Command::YankCodeBlock => "copy code",
+            Command::YankQuote => "copy quote",
+            Command::SaveCodeBlock => "save",
+            Command::ApplyPatch => "apply",
+            Command::RunCodeBlock => "run",
+            Command::ToggleSidebar => "sidebar",
+            Command::GrowSidebar | Command::ShrinkSidebar => "resize sidebar",
+            Command::GrowInput | Command::ShrinkInput => "resize input",
+            Command::ToggleZoom => "zoom",
+            Command::FocusChats => "chats",
+            Command::FocusMessages => "messages",
+            Command::FocusInput => "input",
+            Command::SwitchModel => "model",
+            Command::CycleTheme => "theme",
+            Command::OpenPalette => "commands",
+            Command::Quit => "quit",
+        }
+    }
+
+    /// Commands acting on the chat selected in the sidebar
+    pub fn is_chat_scoped(self) -> bool {
+        matches!(
+            self,
+            Command::RenameChat
+                | Command::DeleteChat
+                | Command::TagChat
+                | Command::MoveChat
+                | Command::TogglePin
+                | Command::ExportChat
+        )
+    }
+}
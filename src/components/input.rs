@@ -28,7 +28,12 @@ impl Input {
 
     pub fn set_value(&mut self, text: String) {
         if text.len() > self.max_length {
-            self.text = text.split_at(self.max_length).0.to_string();
+            let mut end = self.max_length;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            self.text = text[..end].to_string();
         } else {
             self.text = text
         }
@@ -1,7 +1,9 @@
 pub mod input;
 pub mod popup;
+pub mod scroll;
 pub mod stateful_list;
 
 pub use input::*;
 pub use popup::*;
+pub use scroll::*;
 pub use stateful_list::*;
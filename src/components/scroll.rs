@@ -0,0 +1,92 @@
+/// Line offset into content taller than its viewport. The sizes are updated by the
+/// renderer, which also clamps the offset, like `ListState` does for lists.
+#[derive(Debug, Clone)]
+pub struct Scroll {
+    pub offset: usize,
+    /// Stick to the bottom as content grows, turned off by scrolling up
+    pub follow: bool,
+    /// Bring the selection into view on the next render
+    pub reveal: bool,
+    pub content_height: usize,
+    pub viewport_height: usize,
+}
+
+impl Default for Scroll {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            follow: true,
+            reveal: false,
+            content_height: 0,
+            viewport_height: 0,
+        }
+    }
+}
+
+impl Scroll {
+    fn max_offset(&self) -> usize {
+        self.content_height.saturating_sub(self.viewport_height)
+    }
+
+    fn set_offset(&mut self, offset: usize) {
+        self.offset = offset.min(self.max_offset());
+        self.follow = self.offset >= self.max_offset();
+        self.reveal = false;
+    }
+
+    /// Called on every render with the current sizes
+    pub fn update(&mut self, content_height: usize, viewport_height: usize) {
+        self.content_height = content_height;
+        self.viewport_height = viewport_height;
+
+        match self.follow {
+            true => self.offset = self.max_offset(),
+            false => self.offset = self.offset.min(self.max_offset()),
+        }
+    }
+
+    pub fn up(&mut self, lines: usize) {
+        self.set_offset(self.offset.saturating_sub(lines));
+    }
+
+    pub fn down(&mut self, lines: usize) {
+        self.set_offset(self.offset.saturating_add(lines));
+    }
+
+    pub fn page_up(&mut self) {
+        self.up(self.viewport_height.max(1));
+    }
+
+    pub fn page_down(&mut self) {
+        self.down(self.viewport_height.max(1));
+    }
+
+    pub fn half_page_up(&mut self) {
+        self.up((self.viewport_height / 2).max(1));
+    }
+
+    pub fn half_page_down(&mut self) {
+        self.down((self.viewport_height / 2).max(1));
+    }
+
+    pub fn top(&mut self) {
+        self.set_offset(0);
+    }
+
+    pub fn bottom(&mut self) {
+        self.set_offset(self.max_offset());
+    }
+
+    /// Scrolls the least needed to show lines `start..end`, or their start if they don't fit
+    pub fn show(&mut self, start: usize, end: usize) {
+        let offset = if start < self.offset || end - start > self.viewport_height {
+            start
+        } else if end > self.offset + self.viewport_height {
+            end - self.viewport_height
+        } else {
+            self.offset
+        };
+
+        self.set_offset(offset);
+    }
+}
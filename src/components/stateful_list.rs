@@ -1,4 +1,5 @@
 use ratatui::widgets::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Debug, Clone)]
 pub struct StatefulList<T> {
@@ -15,6 +16,19 @@ impl<T> Default for StatefulList<T> {
     }
 }
 
+/// Only the items are persisted, the selection is UI state
+impl<T: Serialize> Serialize for StatefulList<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.items.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for StatefulList<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<T>::deserialize(deserializer).map(StatefulList::with_items)
+    }
+}
+
 impl<T> StatefulList<T> {
     pub fn with_items(items: Vec<T>) -> StatefulList<T> {
         StatefulList {
@@ -46,16 +60,6 @@ impl<T> StatefulList<T> {
         }
     }
 
-    pub fn select_last(&mut self) {
-        if self.items.is_empty() {
-            self.unselect();
-            return;
-        }
-
-        let i = self.items.len() - 1;
-        self.state.select(Some(i));
-    }
-
     pub fn prev(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
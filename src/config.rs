@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::storage::home_dir;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AutoTitleConfig {
+    pub enabled: bool,
+    pub model: String,
+    pub prompt: String,
+}
+
+impl Default for AutoTitleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            model: "gpt-3.5-turbo".to_string(),
+            prompt: "Write a short title (max 5 words) for the conversation above. \
+                     Reply with the title only, no quotes or punctuation."
+                .to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ClipboardConfig {
+    /// Copy through the terminal with OSC 52, works over SSH and in tmux
+    pub osc52: bool,
+    /// Command reading the text on stdin, e.g. `xclip -selection clipboard`.
+    /// Used instead of OSC 52 when set.
+    pub command: Option<String>,
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        Self {
+            osc52: true,
+            command: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RunConfig {
+    /// Programs code blocks may run, `*` allows anything. Empty disables running.
+    /// A program written with a path, e.g. `./x`, must be listed with that path.
+    pub allowlist: Vec<String>,
+    /// Seconds before the command is killed
+    pub timeout: u64,
+    /// Output kept in the chat, in bytes
+    pub max_output: usize,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self {
+            allowlist: vec![],
+            timeout: 30,
+            max_output: 16 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AttachmentsConfig {
+    /// Largest file attached with `@path`, in bytes
+    pub max_file_size: usize,
+    /// Limit for all the text files of a message
+    pub max_total_size: usize,
+    /// Largest PNG or JPEG attached, images are sent to vision models
+    pub max_image_size: usize,
+}
+
+impl Default for AttachmentsConfig {
+    fn default() -> Self {
+        Self {
+            max_file_size: 100 * 1024,
+            max_total_size: 256 * 1024,
+            max_image_size: 5 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ToolsConfig {
+    /// Let the model call the local tools, every call is approved first. Off
+    /// by default, the tool definitions go with every request.
+    pub enabled: bool,
+    /// Longest result sent back to the model, in bytes
+    pub max_result: usize,
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_result: 16 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TranscriptionConfig {
+    /// OpenAI compatible `audio/transcriptions` endpoint, e.g. a local whisper server
+    pub url: String,
+    pub model: String,
+    /// Sent as the bearer token. Without it `OPENAI_API_KEY` is only sent to
+    /// api.openai.com, so other servers never see it.
+    pub api_key: Option<String>,
+    /// Largest audio file sent, in bytes
+    pub max_size: usize,
+}
+
+impl Default for TranscriptionConfig {
+    fn default() -> Self {
+        Self {
+            url: "https://api.openai.com/v1/audio/transcriptions".to_string(),
+            model: "whisper-1".to_string(),
+            api_key: None,
+            max_size: 25 * 1024 * 1024,
+        }
+    }
+}
+
+/// Loaded from `config.toml`, every key is optional
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Model used for chats
+    pub model: String,
+    /// Name of a built-in theme or of a file in `themes/`
+    pub theme: Option<String>,
+    pub auto_title: AutoTitleConfig,
+    pub clipboard: ClipboardConfig,
+    pub run: RunConfig,
+    pub attachments: AttachmentsConfig,
+    pub tools: ToolsConfig,
+    pub transcription: TranscriptionConfig,
+    /// `[keys.<section>]` tables mapping key chords to action names
+    pub keys: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            model: "gpt-3.5-turbo".to_string(),
+            theme: None,
+            auto_title: AutoTitleConfig::default(),
+            clipboard: ClipboardConfig::default(),
+            run: RunConfig::default(),
+            attachments: AttachmentsConfig::default(),
+            tools: ToolsConfig::default(),
+            transcription: TranscriptionConfig::default(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/chatgpt-tui`, falling back to `~/.config/chatgpt-tui`
+pub fn config_dir() -> PathBuf {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir().join(".config"),
+    };
+
+    base.join("chatgpt-tui")
+}
+
+pub fn load() -> anyhow::Result<Config> {
+    let path = config_dir().join("config.toml");
+
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let content = fs::read_to_string(&path)?;
+
+    toml::from_str(&content).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+}
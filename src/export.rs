@@ -0,0 +1,196 @@
+use std::fs;
+use std::path::Path;
+
+use crate::models::{Chat, Role};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            "json" => Some(ExportFormat::Json),
+            "html" | "htm" => Some(ExportFormat::Html),
+            _ => None,
+        }
+    }
+
+    /// Picks the format from the file extension, defaulting to Markdown
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(ExportFormat::from_extension)
+            .unwrap_or(ExportFormat::Markdown)
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Json => "json",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+/// Filesystem friendly version of a chat title
+pub fn slug(title: &str) -> String {
+    let slug: String = title
+        .chars()
+        .map(|c| match c.is_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '-',
+        })
+        .collect();
+
+    let slug = slug
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    match slug.is_empty() {
+        true => "chat".to_string(),
+        false => slug,
+    }
+}
+
+fn role_header(role: Role) -> &'static str {
+    match role {
+        Role::User => "User",
+        Role::Assistant => "Assistant",
+        Role::Tool => "Tool",
+    }
+}
+
+pub fn to_markdown(chats: &[Chat]) -> String {
+    chats
+        .iter()
+        .map(|chat| {
+            let mut out = format!("# {}\n\n", chat.title);
+
+            for message in &chat.messages.items {
+                out.push_str(&format!("### {}\n\n", role_header(message.role)));
+                out.push_str(message.content.trim_end());
+                out.push_str("\n\n");
+            }
+
+            out
+        })
+        .collect::<Vec<_>>()
+        .join("---\n\n")
+}
+
+pub fn to_json(chats: &[Chat]) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(chats)?)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Minimal markdown handling: fenced code blocks become `<pre>`, the rest paragraphs
+fn content_to_html(content: &str) -> String {
+    let mut html = String::new();
+    let mut paragraph: Vec<String> = Vec::new();
+    let mut code: Option<Vec<String>> = None;
+
+    let flush = |paragraph: &mut Vec<String>, html: &mut String| {
+        if !paragraph.is_empty() {
+            html.push_str(&format!("<p>{}</p>\n", paragraph.join("<br>\n")));
+            paragraph.clear();
+        }
+    };
+
+    for line in content.lines() {
+        match (&mut code, line.trim_start().starts_with("```")) {
+            (Some(lines), true) => {
+                html.push_str(&format!(
+                    "<pre><code>{}</code></pre>\n",
+                    escape_html(&lines.join("\n"))
+                ));
+                code = None;
+            }
+            (Some(lines), false) => lines.push(line.to_string()),
+            (None, true) => {
+                flush(&mut paragraph, &mut html);
+                code = Some(Vec::new());
+            }
+            (None, false) if line.trim().is_empty() => flush(&mut paragraph, &mut html),
+            (None, false) => paragraph.push(escape_html(line)),
+        }
+    }
+
+    // unterminated fence
+    if let Some(lines) = code {
+        html.push_str(&format!(
+            "<pre><code>{}</code></pre>\n",
+            escape_html(&lines.join("\n"))
+        ));
+    }
+
+    flush(&mut paragraph, &mut html);
+
+    html
+}
+
+const HTML_STYLE: &str =
+    "body{font-family:sans-serif;max-width:860px;margin:2em auto;padding:0 1em;color:#222}\
+h1{border-bottom:1px solid #ddd;padding-bottom:.3em}\
+.message{border-radius:8px;padding:.5em 1em;margin:1em 0}\
+.user{background:#eef5ff}.assistant{background:#f6f6f6}.tool{background:#fafaf0}\
+.role{font-weight:bold;font-size:.85em;text-transform:uppercase;color:#666}\
+pre{background:#272822;color:#f8f8f2;padding:1em;border-radius:6px;overflow-x:auto}";
+
+pub fn to_html(chats: &[Chat]) -> String {
+    let title = match chats {
+        [chat] => chat.title.clone(),
+        _ => "Chats".to_string(),
+    };
+
+    let mut body = String::new();
+
+    for chat in chats {
+        body.push_str(&format!("<h1>{}</h1>\n", escape_html(&chat.title)));
+
+        for message in &chat.messages.items {
+            body.push_str(&format!(
+                "<div class=\"message {}\"><div class=\"role\">{}</div>\n{}</div>\n",
+                message.role,
+                role_header(message.role),
+                content_to_html(&message.content)
+            ));
+        }
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(&title),
+        HTML_STYLE,
+        body
+    )
+}
+
+/// Writes the chats to `path`, in the format matching its extension
+pub fn export(chats: &[Chat], path: &Path) -> anyhow::Result<()> {
+    let content = match ExportFormat::from_path(path) {
+        ExportFormat::Markdown => to_markdown(chats),
+        ExportFormat::Json => to_json(chats)?,
+        ExportFormat::Html => to_html(chats),
+    };
+
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, content)?;
+
+    Ok(())
+}
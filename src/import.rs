@@ -0,0 +1,322 @@
+//! Importer for the ChatGPT web app data export (`conversations.json`)
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::models::{Chat, Message, Role, TitleSource};
+
+#[derive(Debug, Deserialize)]
+struct Conversation {
+    id: Option<String>,
+    title: Option<String>,
+    create_time: Option<f64>,
+    update_time: Option<f64>,
+    #[serde(default)]
+    mapping: HashMap<String, Node>,
+    current_node: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Node {
+    message: Option<ExportedMessage>,
+    parent: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportedMessage {
+    author: Author,
+    content: Content,
+    create_time: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Author {
+    role: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Content {
+    content_type: String,
+    #[serde(default)]
+    parts: Vec<Value>,
+}
+
+/// What got imported and what had to be left out
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub chats: Vec<Chat>,
+    /// Skipped content, keyed by content type (or role)
+    pub skipped: BTreeMap<String, usize>,
+}
+
+impl ImportReport {
+    fn skip(&mut self, kind: &str) {
+        *self.skipped.entry(kind.to_string()).or_default() += 1;
+    }
+
+    /// Leaves out the conversations already imported into `existing`, so a
+    /// newer export only adds what is new
+    pub fn skip_imported(&mut self, existing: &[Chat]) {
+        let imported: HashSet<&str> = existing
+            .iter()
+            .filter_map(|c| c.source_id.as_deref())
+            .collect();
+
+        let before = self.chats.len();
+        self.chats.retain(|chat| {
+            chat.source_id
+                .as_deref()
+                .is_none_or(|id| !imported.contains(id))
+        });
+
+        for _ in self.chats.len()..before {
+            self.skip("already imported");
+        }
+    }
+
+    /// e.g. `Imported 12 chats (skipped: code x3, system x12)`
+    pub fn summary(&self) -> String {
+        let mut summary = format!("Imported {} chat(s)", self.chats.len());
+
+        if !self.skipped.is_empty() {
+            let skipped: Vec<String> = self
+                .skipped
+                .iter()
+                .map(|(kind, count)| format!("{} x{}", kind, count))
+                .collect();
+
+            summary.push_str(&format!(" (skipped: {})", skipped.join(", ")));
+        }
+
+        summary
+    }
+}
+
+fn timestamp(secs: Option<f64>) -> Option<DateTime<Utc>> {
+    secs.and_then(|s| Utc.timestamp_millis_opt((s * 1000.0) as i64).single())
+}
+
+/// Node ids from the root to `current_node`, i.e. the branch shown in the web app
+fn active_branch(conversation: &Conversation) -> Vec<&str> {
+    let mut branch = Vec::new();
+    let mut current = conversation.current_node.as_deref();
+
+    while let Some(id) = current {
+        // guard against malformed cyclic trees
+        if branch.contains(&id) || branch.len() > conversation.mapping.len() {
+            break;
+        }
+
+        branch.push(id);
+        current = conversation
+            .mapping
+            .get(id)
+            .and_then(|node| node.parent.as_deref());
+    }
+
+    branch.reverse();
+    branch
+}
+
+fn convert(conversation: &Conversation, report: &mut ImportReport) -> Chat {
+    let mut messages = Vec::new();
+
+    for id in active_branch(conversation) {
+        let Some(message) = conversation
+            .mapping
+            .get(id)
+            .and_then(|n| n.message.as_ref())
+        else {
+            continue;
+        };
+
+        let role = match message.author.role.as_str() {
+            "user" => Role::User,
+            "assistant" => Role::Assistant,
+            other => {
+                report.skip(other);
+                continue;
+            }
+        };
+
+        if !matches!(
+            message.content.content_type.as_str(),
+            "text" | "multimodal_text"
+        ) {
+            report.skip(&message.content.content_type);
+            continue;
+        }
+
+        let mut text = Vec::new();
+
+        for part in &message.content.parts {
+            match part {
+                Value::String(s) => text.push(s.as_str()),
+                Value::Object(o) => {
+                    let kind = o
+                        .get("content_type")
+                        .and_then(Value::as_str)
+                        .unwrap_or("unknown part");
+                    report.skip(kind);
+                }
+                _ => report.skip("unknown part"),
+            }
+        }
+
+        let content = text.join("\n");
+
+        // hidden system/context messages come through as empty user/assistant turns
+        if content.trim().is_empty() {
+            continue;
+        }
+
+        let mut m = Message::new(role, &content);
+
+        if let Some(created_at) = timestamp(message.create_time) {
+            m.created_at = created_at;
+        }
+
+        messages.push(m);
+    }
+
+    let title = conversation.title.as_deref().unwrap_or("imported chat");
+    let mut chat = Chat::with_messages(title, messages);
+    chat.title_source = TitleSource::User;
+    chat.source_id = conversation.id.clone();
+
+    if let Some(created_at) = timestamp(conversation.create_time) {
+        chat.created_at = created_at;
+    }
+
+    if let Some(updated_at) = timestamp(conversation.update_time) {
+        chat.updated_at = updated_at;
+    }
+
+    chat
+}
+
+pub fn import_conversations(path: &Path) -> anyhow::Result<ImportReport> {
+    import_json(&fs::read_to_string(path)?)
+}
+
+fn import_json(content: &str) -> anyhow::Result<ImportReport> {
+    let conversations: Vec<Conversation> = serde_json::from_str(content)?;
+
+    let mut report = ImportReport::default();
+
+    for conversation in &conversations {
+        let chat = convert(conversation, &mut report);
+
+        if chat.messages.items.is_empty() {
+            report.skip("empty conversation");
+            continue;
+        }
+
+        report.chats.push(chat);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two answers to the same question, the second one is the active branch
+    const EXPORT: &str = r#"[{
+        "id": "conv-1",
+        "title": "Greetings",
+        "create_time": 1700000000.5,
+        "current_node": "retry",
+        "mapping": {
+            "root": { "message": null, "parent": null },
+            "system": {
+                "message": {
+                    "author": { "role": "system" },
+                    "content": { "content_type": "text", "parts": ["be nice"] }
+                },
+                "parent": "root"
+            },
+            "question": {
+                "message": {
+                    "author": { "role": "user" },
+                    "content": { "content_type": "text", "parts": ["hi"] }
+                },
+                "parent": "system"
+            },
+            "answer": {
+                "message": {
+                    "author": { "role": "assistant" },
+                    "content": { "content_type": "text", "parts": ["first answer"] }
+                },
+                "parent": "question"
+            },
+            "retry": {
+                "message": {
+                    "author": { "role": "assistant" },
+                    "content": { "content_type": "text", "parts": ["second answer"] }
+                },
+                "parent": "question"
+            }
+        }
+    }]"#;
+
+    #[test]
+    fn the_active_branch_is_walked_from_current_node() {
+        let conversations: Vec<Conversation> = serde_json::from_str(EXPORT).unwrap();
+
+        assert_eq!(
+            active_branch(&conversations[0]),
+            ["root", "system", "question", "retry"]
+        );
+
+        let report = import_json(EXPORT).unwrap();
+        let chat = &report.chats[0];
+        let contents: Vec<&str> = chat
+            .messages
+            .items
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+
+        assert_eq!(contents, ["hi", "second answer"]);
+        assert_eq!(chat.title, "Greetings");
+        assert_eq!(chat.source_id.as_deref(), Some("conv-1"));
+        assert_eq!(report.summary(), "Imported 1 chat(s) (skipped: system x1)");
+    }
+
+    #[test]
+    fn cycles_end_the_walk() {
+        let conversations: Vec<Conversation> = serde_json::from_str(
+            r#"[{
+                "current_node": "a",
+                "mapping": {
+                    "a": { "message": null, "parent": "b" },
+                    "b": { "message": null, "parent": "a" }
+                }
+            }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(active_branch(&conversations[0]), ["b", "a"]);
+    }
+
+    #[test]
+    fn imported_conversations_are_skipped() {
+        let existing = import_json(EXPORT).unwrap().chats;
+
+        let mut report = import_json(EXPORT).unwrap();
+        report.skip_imported(&existing);
+
+        assert!(report.chats.is_empty());
+        assert_eq!(
+            report.summary(),
+            "Imported 0 chat(s) (skipped: already imported x1, system x1)"
+        );
+    }
+}
@@ -0,0 +1,716 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::commands::Command;
+use crate::state::{Action, Section};
+
+/// A key plus its modifiers, e.g. `ctrl-p`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // the case of a char carries shift, so `shift-n` is `N`
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                let mut upper = c.to_uppercase();
+                match (upper.next(), upper.next()) {
+                    (Some(upper), None) => KeyCode::Char(upper),
+                    _ => code,
+                }
+            }
+            code => code,
+        };
+
+        let modifiers = match code {
+            KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+
+        Self { code, modifiers }
+    }
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(key: KeyEvent) -> Self {
+        KeyChord::new(key.code, key.modifiers)
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    /// Accepts `ctrl-p`, `alt+x`, `shift-tab`, `N` or `shift-n`, `enter`, `pgdn`, `f5`...
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut key = s;
+
+        while let Some((modifier, rest)) = key.split_once(['-', '+']).filter(|(_, r)| !r.is_empty())
+        {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier `{}` in `{}`", modifier, s)),
+            };
+            key = rest;
+        }
+
+        let code = match key.to_lowercase().as_str() {
+            _ if key.chars().count() == 1 => KeyCode::Char(key.chars().next().unwrap_or(' ')),
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            f if f.starts_with('f') && f[1..].parse::<u8>().is_ok() => {
+                KeyCode::F(f[1..].parse().unwrap_or(1))
+            }
+            _ => return Err(format!("unknown key `{}`", s)),
+        };
+
+        Ok(KeyChord::new(code, modifiers))
+    }
+}
+
+impl Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift-")?;
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// Where a binding applies, `Navigation` is when no section is focused
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyContext {
+    Global,
+    Navigation,
+    Chats,
+    Messages,
+    Input,
+    Viewer,
+    Modal,
+}
+
+impl KeyContext {
+    pub const ALL: [KeyContext; 7] = [
+        KeyContext::Global,
+        KeyContext::Navigation,
+        KeyContext::Chats,
+        KeyContext::Messages,
+        KeyContext::Input,
+        KeyContext::Viewer,
+        KeyContext::Modal,
+    ];
+
+    pub fn from_focus(focus: Option<Section>) -> Self {
+        match focus {
+            None => KeyContext::Navigation,
+            Some(Section::Chats) => KeyContext::Chats,
+            Some(Section::Messages) => KeyContext::Messages,
+            Some(Section::Input) => KeyContext::Input,
+            Some(Section::Viewer) => KeyContext::Viewer,
+            Some(Section::Modal) => KeyContext::Modal,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyContext::Global => "global",
+            KeyContext::Navigation => "navigation",
+            KeyContext::Chats => "chats",
+            KeyContext::Messages => "messages",
+            KeyContext::Input => "input",
+            KeyContext::Viewer => "viewer",
+            KeyContext::Modal => "modal",
+        }
+    }
+
+    /// Contexts where plain characters are text, so global bindings need a modifier
+    fn is_typing(self) -> bool {
+        matches!(self, KeyContext::Input | KeyContext::Modal)
+    }
+}
+
+/// Parses an action name from the config, either a movement or a `Command` name
+pub fn parse_action(name: &str) -> Option<Action> {
+    let action = match name {
+        "up" => Action::Up,
+        "down" => Action::Down,
+        "left" => Action::Left,
+        "right" => Action::Right,
+        "enter" => Action::Enter,
+        "esc" => Action::Esc,
+        "backspace" => Action::Backspace,
+        "complete" => Action::Key(KeyCode::Tab),
+        name => Action::Command(Command::from_name(name)?),
+    };
+
+    Some(action)
+}
+
+/// Short description of what an action does in a context, for the help bar
+fn action_label(action: &Action, context: KeyContext) -> String {
+    match action {
+        Action::Up | Action::Down => match context {
+            KeyContext::Viewer => "scroll",
+            _ => "move",
+        }
+        .to_string(),
+        Action::Left | Action::Right => match context {
+            KeyContext::Viewer => "pan",
+            _ => "cursor",
+        }
+        .to_string(),
+        Action::Enter => match context {
+            KeyContext::Navigation => "focus",
+            KeyContext::Chats => "open",
+            _ => "submit",
+        }
+        .to_string(),
+        Action::Esc => match context {
+            KeyContext::Modal => "cancel",
+            KeyContext::Viewer => "close",
+            KeyContext::Navigation => "back",
+            _ => "blur",
+        }
+        .to_string(),
+        Action::Backspace => "delete".to_string(),
+        Action::Key(KeyCode::Tab) => "complete".to_string(),
+        Action::Command(command) => command.label().to_string(),
+        Action::Char(c) => c.to_string(),
+        Action::Key(code) => format!("{:?}", code),
+    }
+}
+
+const DEFAULT_BINDINGS: &[(KeyContext, &[(&str, &str)])] = &[
+    (
+        KeyContext::Global,
+        &[
+            ("ctrl-p", "open_palette"),
+            ("ctrl-t", "cycle_theme"),
+            ("ctrl-b", "toggle_sidebar"),
+            ("ctrl-z", "toggle_zoom"),
+            ("alt-right", "grow_sidebar"),
+            ("alt-left", "shrink_sidebar"),
+            ("alt-up", "grow_input"),
+            ("alt-down", "shrink_input"),
+        ],
+    ),
+    (
+        KeyContext::Navigation,
+        &[
+            ("enter", "enter"),
+            ("esc", "esc"),
+            ("k", "up"),
+            ("up", "up"),
+            ("j", "down"),
+            ("down", "down"),
+            ("h", "left"),
+            ("left", "left"),
+            ("l", "right"),
+            ("right", "right"),
+            ("c", "focus_chats"),
+            ("m", "focus_messages"),
+            ("i", "focus_input"),
+            ("b", "toggle_sidebar"),
+            ("z", "toggle_zoom"),
+            (">", "grow_sidebar"),
+            ("<", "shrink_sidebar"),
+            ("+", "grow_input"),
+            ("-", "shrink_input"),
+            (":", "open_palette"),
+            ("q", "quit"),
+        ],
+    ),
+    (
+        KeyContext::Chats,
+        &[
+            ("esc", "esc"),
+            ("k", "up"),
+            ("up", "up"),
+            ("j", "down"),
+            ("down", "down"),
+            ("enter", "enter"),
+            ("n", "new_chat"),
+            ("N", "quick_new_chat"),
+            ("r", "rename_chat"),
+            ("t", "tag_chat"),
+            ("m", "move_chat"),
+            ("f", "filter_by_tag"),
+            ("p", "toggle_pin"),
+            ("s", "cycle_sort"),
+            ("e", "export_chat"),
+            ("E", "export_all_chats"),
+            ("I", "import_chats"),
+            ("backspace", "delete_chat"),
+            (":", "open_palette"),
+            ("q", "quit"),
+        ],
+    ),
+    (
+        KeyContext::Messages,
+        &[
+            ("esc", "esc"),
+            ("k", "up"),
+            ("up", "up"),
+            ("j", "down"),
+            ("down", "down"),
+            ("/", "find"),
+            ("n", "next_match"),
+            ("N", "prev_match"),
+            ("backspace", "delete_message"),
+            ("enter", "view_message"),
+            ("v", "view_message"),
+            ("|", "open_in_pager"),
+            ("y", "yank_message"),
+            ("Y", "yank_code_block"),
+            (">", "yank_quote"),
+            ("s", "save_code_block"),
+            ("a", "apply_patch"),
+            ("r", "run_code_block"),
+            ("ctrl-y", "scroll_up"),
+            ("ctrl-e", "scroll_down"),
+            ("pgup", "scroll_page_up"),
+            ("pgdn", "scroll_page_down"),
+            ("ctrl-u", "scroll_half_page_up"),
+            ("ctrl-d", "scroll_half_page_down"),
+            ("g", "scroll_top"),
+            ("G", "scroll_bottom"),
+            (":", "open_palette"),
+            ("q", "quit"),
+        ],
+    ),
+    (
+        KeyContext::Input,
+        &[
+            ("esc", "esc"),
+            ("enter", "enter"),
+            ("tab", "complete"),
+            ("left", "left"),
+            ("right", "right"),
+            ("backspace", "backspace"),
+            ("pgup", "scroll_page_up"),
+            ("pgdn", "scroll_page_down"),
+        ],
+    ),
+    (
+        KeyContext::Viewer,
+        &[
+            ("esc", "esc"),
+            ("q", "esc"),
+            ("k", "up"),
+            ("up", "up"),
+            ("j", "down"),
+            ("down", "down"),
+            ("h", "left"),
+            ("left", "left"),
+            ("l", "right"),
+            ("right", "right"),
+            ("pgup", "scroll_page_up"),
+            ("pgdn", "scroll_page_down"),
+            ("ctrl-u", "scroll_half_page_up"),
+            ("ctrl-d", "scroll_half_page_down"),
+            ("g", "scroll_top"),
+            ("G", "scroll_bottom"),
+            ("w", "toggle_wrap"),
+            ("|", "open_in_pager"),
+            ("y", "yank_message"),
+            ("Y", "yank_code_block"),
+            (">", "yank_quote"),
+            ("s", "save_code_block"),
+            ("a", "apply_patch"),
+            ("r", "run_code_block"),
+        ],
+    ),
+    (
+        KeyContext::Modal,
+        &[
+            ("esc", "esc"),
+            ("enter", "enter"),
+            ("up", "up"),
+            ("down", "down"),
+            ("left", "left"),
+            ("right", "right"),
+            ("backspace", "backspace"),
+        ],
+    ),
+];
+
+/// Key chords per context, built from the defaults and the `[keys.<context>]` config tables
+#[derive(Clone, Debug, Default)]
+pub struct Keymap {
+    bindings: BTreeMap<KeyContext, Vec<(KeyChord, Action)>>,
+    /// Problems found while loading the user bindings
+    pub warnings: Vec<String>,
+}
+
+impl Keymap {
+    fn bind(&mut self, context: KeyContext, chord: KeyChord, action: Action) {
+        let bindings = self.bindings.entry(context).or_default();
+
+        match bindings.iter_mut().find(|(c, _)| *c == chord) {
+            Some(binding) => binding.1 = action,
+            None => bindings.push((chord, action)),
+        }
+    }
+
+    fn bound(&self, context: KeyContext, chord: KeyChord) -> Option<&Action> {
+        self.bindings
+            .get(&context)
+            .and_then(|b| b.iter().find(|(c, _)| *c == chord))
+            .map(|(_, action)| action)
+    }
+
+    fn unbind(&mut self, context: KeyContext, chord: KeyChord) {
+        if let Some(bindings) = self.bindings.get_mut(&context) {
+            bindings.retain(|(c, _)| *c != chord);
+        }
+    }
+
+    /// User tables are keyed by context name, then key chord, e.g. `"ctrl-n" = "new_chat"`.
+    /// Binding to `"none"` removes a default binding.
+    pub fn new(user: &BTreeMap<String, BTreeMap<String, String>>) -> Self {
+        let mut keymap = Keymap::default();
+
+        for (context, bindings) in DEFAULT_BINDINGS {
+            for (key, action) in bindings.iter() {
+                if let (Ok(chord), Some(action)) = (key.parse(), parse_action(action)) {
+                    keymap.bind(*context, chord, action);
+                }
+            }
+        }
+
+        for (context_name, bindings) in user {
+            let Some(context) = KeyContext::ALL
+                .into_iter()
+                .find(|c| c.name() == context_name)
+            else {
+                keymap
+                    .warnings
+                    .push(format!("unknown key section `{}`", context_name));
+                continue;
+            };
+
+            // different spellings of the same chord, e.g. `ctrl-n` and `control+n`
+            let mut seen: Vec<(KeyChord, &str)> = Vec::new();
+
+            for (key, action_name) in bindings {
+                let chord: KeyChord = match key.parse() {
+                    Ok(chord) => chord,
+                    Err(e) => {
+                        keymap
+                            .warnings
+                            .push(format!("[keys.{}] {}", context_name, e));
+                        continue;
+                    }
+                };
+
+                let duplicate = seen.iter().find(|(c, _)| *c == chord);
+
+                if let Some((_, other)) = duplicate {
+                    keymap.warnings.push(format!(
+                        "[keys.{}] `{}` and `{}` are the same key",
+                        context_name, other, key
+                    ));
+                }
+
+                let is_duplicate = duplicate.is_some();
+                seen.push((chord, key));
+
+                if action_name == "none" {
+                    keymap.unbind(context, chord);
+                    continue;
+                }
+
+                match parse_action(action_name) {
+                    Some(action) => {
+                        let replaced = keymap
+                            .bound(context, chord)
+                            .filter(|bound| **bound != action && !is_duplicate)
+                            .map(|bound| action_label(bound, context));
+
+                        // rebinding a key by mistake silently loses its default
+                        if let Some(replaced) = replaced {
+                            keymap.warnings.push(format!(
+                                "[keys.{}] `{}` replaces the default binding to {}",
+                                context_name, key, replaced
+                            ));
+                        }
+
+                        keymap.bind(context, chord, action);
+                    }
+                    None => keymap.warnings.push(format!(
+                        "[keys.{}] unknown action `{}`",
+                        context_name, action_name
+                    )),
+                }
+            }
+        }
+
+        keymap.check_global_conflicts();
+        keymap
+    }
+
+    /// Section bindings win over global ones, flag the global bindings that get hidden
+    fn check_global_conflicts(&mut self) {
+        let Some(global) = self.bindings.get(&KeyContext::Global) else {
+            return;
+        };
+
+        for (chord, action) in global {
+            for context in KeyContext::ALL.into_iter().skip(1) {
+                let shadowing = self
+                    .bindings
+                    .get(&context)
+                    .and_then(|b| b.iter().find(|(c, _)| c == chord));
+
+                if let Some((_, other)) = shadowing.filter(|(_, other)| other != action) {
+                    self.warnings.push(format!(
+                        "`{}` is bound to {} globally but to {} in {}",
+                        chord,
+                        action_label(action, KeyContext::Global),
+                        action_label(other, context),
+                        context.name()
+                    ));
+                }
+            }
+        }
+    }
+
+    pub fn resolve(&self, context: KeyContext, key: KeyEvent) -> Option<Action> {
+        self.resolve_chord(context, KeyChord::from(key))
+    }
+
+    fn resolve_chord(&self, context: KeyContext, chord: KeyChord) -> Option<Action> {
+        let find = |context: KeyContext| self.bound(context, chord).cloned();
+
+        let global = match context.is_typing() && chord.modifiers.is_empty() {
+            true => None,
+            false => find(KeyContext::Global),
+        };
+
+        find(context).or(global)
+    }
+
+    /// Turns a key event into an action: the bound one, or the raw key
+    pub fn action_for(&self, context: KeyContext, key: KeyEvent) -> Option<Action> {
+        if let Some(action) = self.resolve(context, key) {
+            return Some(action);
+        }
+
+        let has_modifiers = key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+
+        match key.code {
+            KeyCode::Char(_) if has_modifiers => None,
+            KeyCode::Char(c) => Some(Action::Char(c)),
+            code => Some(Action::Key(code)),
+        }
+    }
+
+    /// Every chord bound to `action`, in any context
+    pub fn keys_for(&self, action: &Action) -> Vec<KeyChord> {
+        let mut keys: Vec<KeyChord> = Vec::new();
+
+        for bindings in self.bindings.values() {
+            for (chord, a) in bindings {
+                if a == action && !keys.contains(chord) {
+                    keys.push(*chord);
+                }
+            }
+        }
+
+        keys
+    }
+
+    /// `(keys, label)` pairs for the help bar, one per distinct label
+    pub fn help(&self, context: KeyContext) -> Vec<(String, String)> {
+        let mut entries: Vec<(Vec<String>, String)> = Vec::new();
+
+        let section = self.bindings.get(&context).into_iter().flatten();
+        let global = self.bindings.get(&KeyContext::Global).into_iter().flatten();
+
+        let is_arrow = |chord: &KeyChord| {
+            matches!(
+                chord.code,
+                KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right
+            )
+        };
+
+        for (chord, action) in section.chain(global) {
+            // only list global bindings that are not shadowed by the section
+            if self.resolve_chord(context, *chord).as_ref() != Some(action) {
+                continue;
+            }
+
+            let label = action_label(action, context);
+            let key = chord.to_string();
+
+            match entries.iter_mut().find(|(_, l)| *l == label) {
+                // arrows are implied when there are letter bindings for the same thing
+                Some((keys, _))
+                    if is_arrow(chord)
+                        && keys.iter().any(|k| !k.ends_with(['↑', '↓', '←', '→'])) => {}
+                Some((keys, _)) => {
+                    if !keys.contains(&key) {
+                        keys.push(key)
+                    }
+                }
+                None => entries.push((vec![key], label)),
+            }
+        }
+
+        entries
+            .into_iter()
+            .map(|(keys, label)| (keys.join("/"), label))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(s: &str) -> KeyChord {
+        s.parse().unwrap()
+    }
+
+    fn keymap(context: &str, bindings: &[(&str, &str)]) -> Keymap {
+        let bindings = bindings
+            .iter()
+            .map(|(key, action)| (key.to_string(), action.to_string()))
+            .collect();
+
+        Keymap::new(&BTreeMap::from([(context.to_string(), bindings)]))
+    }
+
+    #[test]
+    fn chords_are_parsed() {
+        let ctrl_p = KeyChord::new(KeyCode::Char('p'), KeyModifiers::CONTROL);
+
+        assert_eq!(chord("ctrl-p"), ctrl_p);
+        assert_eq!(chord("Control+p"), ctrl_p);
+        assert_eq!(
+            chord("alt-shift-tab"),
+            KeyChord::new(KeyCode::Tab, KeyModifiers::ALT | KeyModifiers::SHIFT)
+        );
+        assert_eq!(chord("pgdn").code, KeyCode::PageDown);
+        assert_eq!(chord("f5").code, KeyCode::F(5));
+        assert_eq!(chord("space").code, KeyCode::Char(' '));
+        assert_eq!(chord("-").code, KeyCode::Char('-'));
+
+        assert!("hyper-x".parse::<KeyChord>().is_err());
+        assert!("c-x".parse::<KeyChord>().is_err());
+        assert!("ctrl-nope".parse::<KeyChord>().is_err());
+    }
+
+    #[test]
+    fn shift_is_carried_by_the_case_of_chars() {
+        assert_eq!(chord("shift-n"), chord("N"));
+        assert_eq!(chord("N").modifiers, KeyModifiers::NONE);
+        assert_eq!(chord("ctrl-shift-n"), chord("ctrl-N"));
+
+        // terminals report `N` with or without shift
+        let event = KeyEvent::new(KeyCode::Char('N'), KeyModifiers::SHIFT);
+        assert_eq!(KeyChord::from(event), chord("N"));
+        assert_eq!(chord("N").to_string(), "N");
+
+        // keys without a case keep shift
+        assert_eq!(chord("shift-tab").modifiers, KeyModifiers::SHIFT);
+    }
+
+    #[test]
+    fn none_removes_a_default_binding() {
+        let keymap = keymap("chats", &[("n", "none")]);
+
+        assert_eq!(keymap.resolve_chord(KeyContext::Chats, chord("n")), None);
+        assert!(keymap.warnings.is_empty(), "{:?}", keymap.warnings);
+    }
+
+    #[test]
+    fn replacing_a_default_warns() {
+        let keymap = keymap("chats", &[("q", "new_chat"), ("n", "new_chat")]);
+
+        assert_eq!(
+            keymap.resolve_chord(KeyContext::Chats, chord("q")),
+            Some(Action::Command(Command::NewChat))
+        );
+        assert_eq!(
+            keymap.warnings,
+            ["[keys.chats] `q` replaces the default binding to quit"]
+        );
+    }
+
+    #[test]
+    fn spellings_of_the_same_key_warn_once() {
+        let keymap = keymap("chats", &[("ctrl-n", "new_chat"), ("control+n", "quit")]);
+
+        assert_eq!(
+            keymap.warnings,
+            ["[keys.chats] `control+n` and `ctrl-n` are the same key"]
+        );
+    }
+
+    #[test]
+    fn unknown_sections_and_actions_warn() {
+        assert_eq!(
+            keymap("sidebar", &[("x", "quit")]).warnings,
+            ["unknown key section `sidebar`"]
+        );
+        assert_eq!(
+            keymap("chats", &[("x", "explode")]).warnings,
+            ["[keys.chats] unknown action `explode`"]
+        );
+    }
+
+    #[test]
+    fn shadowed_global_bindings_warn() {
+        let keymap = keymap("global", &[("ctrl-y", "quit")]);
+
+        assert_eq!(
+            keymap.warnings,
+            ["`Ctrl-y` is bound to quit globally but to scroll in messages"]
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use serde::{Deserialize, Serialize};
+
+use crate::state::Section;
+
+const SIDEBAR_WIDTH: (u16, u16) = (10, 60);
+const INPUT_HEIGHT: (u16, u16) = (3, 15);
+
+/// Pane sizes picked by the user, persisted with the chats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaneLayout {
+    pub sidebar: bool,
+    /// Percentage of the terminal width
+    pub sidebar_width: u16,
+    /// Lines, borders included
+    pub input_height: u16,
+    /// Only the focused pane is drawn, not persisted
+    #[serde(skip)]
+    pub zoom: bool,
+}
+
+impl Default for PaneLayout {
+    fn default() -> Self {
+        Self {
+            sidebar: true,
+            sidebar_width: 25,
+            input_height: 3,
+            zoom: false,
+        }
+    }
+}
+
+impl PaneLayout {
+    pub fn resize_sidebar(&mut self, delta: i16) {
+        self.sidebar = true;
+        self.sidebar_width = self
+            .sidebar_width
+            .saturating_add_signed(delta)
+            .clamp(SIDEBAR_WIDTH.0, SIDEBAR_WIDTH.1);
+    }
+
+    pub fn resize_input(&mut self, delta: i16) {
+        self.input_height = self
+            .input_height
+            .saturating_add_signed(delta)
+            .clamp(INPUT_HEIGHT.0, INPUT_HEIGHT.1);
+    }
+}
+
+/// Where each pane goes, `None` for the hidden ones
+#[derive(Debug, Clone, Copy)]
+pub struct Panes {
+    pub chats: Option<Rect>,
+    pub help: Rect,
+    pub messages: Option<Rect>,
+    pub input: Option<Rect>,
+}
+
+/// Smallest terminal the UI is drawn in, below it a notice replaces everything
+pub const MIN_WIDTH: u16 = 30;
+pub const MIN_HEIGHT: u16 = 8;
+/// Below this width a single pane is shown at a time
+const STACK_WIDTH: u16 = 70;
+const SIDEBAR_COLUMNS: (u16, u16) = (20, 50);
+const MESSAGES_MIN_HEIGHT: u16 = 3;
+
+pub fn too_small(area: Rect) -> bool {
+    area.width < MIN_WIDTH || area.height < MIN_HEIGHT
+}
+
+/// Splits the terminal for `layout`. A hidden sidebar still shows up while the
+/// chats section is selected. Zoom keeps only the `section` pane, or the whole
+/// conversation column for the input. Narrow terminals are always zoomed, on the
+/// sidebar or the conversation.
+pub fn panes(area: Rect, layout: &PaneLayout, section: Section) -> Panes {
+    let stacked = area.width < STACK_WIDTH;
+
+    // modals are drawn over the unzoomed layout
+    let zoomed = match (layout.zoom, stacked, section) {
+        (true, _, Section::Chats | Section::Messages | Section::Input) => Some(section),
+        (_, true, Section::Chats) => Some(Section::Chats),
+        (_, true, _) => Some(Section::Input),
+        _ => None,
+    };
+
+    let show_chats = match zoomed {
+        Some(zoomed) => zoomed == Section::Chats,
+        None => layout.sidebar || section == Section::Chats,
+    };
+    let show_messages = matches!(zoomed, None | Some(Section::Messages | Section::Input));
+    let show_input = matches!(zoomed, None | Some(Section::Input));
+
+    let (chats, main) = match (show_chats, show_messages) {
+        (true, true) => {
+            let sidebar = (area.width as u32 * layout.sidebar_width as u32 / 100) as u16;
+            let sidebar = sidebar
+                .clamp(SIDEBAR_COLUMNS.0, SIDEBAR_COLUMNS.1)
+                .min(area.width.saturating_sub(MIN_WIDTH));
+
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(sidebar), Constraint::Min(MIN_WIDTH)])
+                .split(area);
+
+            (Some(columns[0]), columns[1])
+        }
+        (true, false) => (Some(area), area),
+        (false, _) => (None, area),
+    };
+
+    // the help wraps on a second line when there is room for it
+    let help_height = if area.height >= 30 { 2 } else { 1 };
+    let input_height = match show_input {
+        true => layout
+            .input_height
+            .min(
+                main.height
+                    .saturating_sub(help_height + MESSAGES_MIN_HEIGHT),
+            )
+            .max(INPUT_HEIGHT.0),
+        false => 0,
+    };
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(help_height),
+            Constraint::Min(MESSAGES_MIN_HEIGHT),
+            Constraint::Length(input_height),
+        ])
+        .split(main);
+
+    // a zoomed sidebar still gets the help line
+    let (chats, help) = match (chats, show_messages) {
+        (Some(chats), false) => {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(help_height), Constraint::Min(0)])
+                .split(chats);
+
+            (Some(rows[1]), rows[0])
+        }
+        (chats, _) => (chats, rows[0]),
+    };
+
+    Panes {
+        chats,
+        help,
+        messages: show_messages.then_some(rows[1]),
+        input: show_input.then_some(rows[2]),
+    }
+}
+
+/// A `width` x `height` box centered horizontally in the upper part of `area`,
+/// shrunk to fit
+pub fn popup(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 3,
+        width,
+        height,
+    }
+}
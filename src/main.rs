@@ -1,19 +1,40 @@
+mod attach;
+mod cli;
+mod clipboard;
+mod commands;
 mod components;
+mod config;
 mod cursor;
+mod export;
+mod import;
+mod keymap;
+mod layout;
+mod markdown;
 mod models;
+mod mouse;
 mod openai;
+mod patch;
+mod run;
+mod save;
+mod search;
+mod sidebar;
+mod slash;
 mod state;
+mod storage;
+mod theme;
+mod tools;
 mod ui;
 mod utils;
+mod viewer;
 
 use std::io;
 
+use crate::keymap::{KeyContext, Keymap};
 use crate::state::*;
 use crate::ui::render;
 
 use crossterm::{
-    event::{self, KeyCode},
-    execute,
+    event, execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
@@ -21,9 +42,20 @@ use ratatui::{backend::Backend, prelude::*};
 
 use crossterm::event::{DisableMouseCapture, EnableMouseCapture, Event};
 use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
+    if let Some(command) = cli::parse(std::env::args()).map_err(|e| color_eyre::eyre::eyre!(e))? {
+        cli::run(command).await;
+    }
+
+    let config = config::load().map_err(|e| color_eyre::eyre::eyre!(e))?;
+
+    let (saved_state, state_warning) =
+        storage::load_or_recover().map_err(|e| color_eyre::eyre::eyre!(e))?;
+    let _lock = storage::lock().map_err(|e| color_eyre::eyre::eyre!(e))?;
+
     enable_raw_mode()?;
 
     utils::setup_panic_handler()?;
@@ -36,10 +68,40 @@ async fn main() -> color_eyre::Result<()> {
     terminal.clear()?;
 
     // logic
-    let app = App::default();
+    let mut app = App::default();
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+
+    let (themes, mut theme_errors) = theme::load_themes(&config::config_dir().join("themes"));
+    let theme_name = config.theme.as_deref().unwrap_or("dark");
+
+    app.keymap = Keymap::new(&config.keys);
+    app.themes = themes;
+
+    if !app.set_theme(theme_name) {
+        theme_errors.push(format!("unknown theme `{}`", theme_name));
+        app.set_theme("dark");
+    }
+    app.config = config;
+
+    let mut warnings: Vec<String> = state_warning.into_iter().collect();
+
+    if !app.keymap.warnings.is_empty() {
+        warnings.push(format!("Key bindings: {}", app.keymap.warnings.join("; ")));
+    }
+
+    if !theme_errors.is_empty() {
+        warnings.push(format!("Themes: {}", theme_errors.join("; ")));
+    }
+
+    if !warnings.is_empty() {
+        app.notice = Some(warnings.join(" | "));
+    }
+
+    app.events = Some(events_tx);
+    app.restore(saved_state);
     let tick_rate = Duration::from_millis(250);
 
-    let res = run_app(&mut terminal, app, tick_rate).await;
+    let res = run_app(&mut terminal, app, events_rx, tick_rate).await;
 
     disable_raw_mode()?;
     execute!(
@@ -59,76 +121,50 @@ async fn main() -> color_eyre::Result<()> {
 async fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     mut app: App,
+    mut events: UnboundedReceiver<AppEvent>,
     tick_rate: Duration,
 ) -> anyhow::Result<()> {
-    app.chats.select_first();
+    app.select_first_sidebar_row();
 
     let mut last_tick = Instant::now();
 
     loop {
+        while let Ok(event) = events.try_recv() {
+            app.handle_event(event).await?;
+            app.persist();
+        }
+
         terminal.draw(|f| render(f, &mut app))?;
 
         let elapsed = last_tick.elapsed();
         let timeout = tick_rate.saturating_sub(elapsed);
 
         if event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                match &app.focus {
-                    Some(s) => match s {
-                        Section::Modal => match key.code {
-                            KeyCode::Enter => app.dispatch(Action::Enter).await?,
-                            KeyCode::Esc => app.dispatch(Action::Esc).await?,
-                            KeyCode::Left => app.dispatch(Action::Left).await?,
-                            KeyCode::Down => app.dispatch(Action::Down).await?,
-                            KeyCode::Up => app.dispatch(Action::Up).await?,
-                            KeyCode::Right => app.dispatch(Action::Right).await?,
-                            KeyCode::Char(to_enter) => app.dispatch(Action::Char(to_enter)).await?,
-                            KeyCode::Backspace => app.dispatch(Action::Backspace).await?,
-                            keycode => app.dispatch(Action::Key(keycode)).await?,
-                        },
-                        Section::Input => match key.code {
-                            KeyCode::Enter => app.dispatch(Action::Enter).await?,
-                            KeyCode::Esc => app.dispatch(Action::Esc).await?,
-                            KeyCode::Left => app.dispatch(Action::Left).await?,
-                            KeyCode::Down => app.dispatch(Action::Down).await?,
-                            KeyCode::Up => app.dispatch(Action::Up).await?,
-                            KeyCode::Right => app.dispatch(Action::Right).await?,
-                            KeyCode::Char(to_enter) => app.dispatch(Action::Char(to_enter)).await?,
-                            KeyCode::Backspace => app.dispatch(Action::Backspace).await?,
-                            keycode => app.dispatch(Action::Key(keycode)).await?,
-                        },
-                        _ => match key.code {
-                            KeyCode::Char('q') => return Ok(()),
-                            KeyCode::Enter => app.dispatch(Action::Enter).await?,
-                            KeyCode::Esc => app.dispatch(Action::Esc).await?,
-                            KeyCode::Backspace => app.dispatch(Action::Backspace).await?,
-                            KeyCode::Left | KeyCode::Char('h') => {
-                                app.dispatch(Action::Left).await?
-                            }
-                            KeyCode::Down | KeyCode::Char('j') => {
-                                app.dispatch(Action::Down).await?
-                            }
-                            KeyCode::Up | KeyCode::Char('k') => app.dispatch(Action::Up).await?,
-                            KeyCode::Right | KeyCode::Char('l') => {
-                                app.dispatch(Action::Right).await?
-                            }
-                            KeyCode::Char(to_enter) => app.dispatch(Action::Char(to_enter)).await?,
-                            keycode => app.dispatch(Action::Key(keycode)).await?,
-                        },
-                    },
-                    None => match key.code {
-                        KeyCode::Char('q') => return Ok(()),
-                        KeyCode::Enter => app.dispatch(Action::Enter).await?,
-                        KeyCode::Esc => app.dispatch(Action::Esc).await?,
-                        KeyCode::Backspace => app.dispatch(Action::Backspace).await?,
-                        KeyCode::Left | KeyCode::Char('h') => app.dispatch(Action::Left).await?,
-                        KeyCode::Down | KeyCode::Char('j') => app.dispatch(Action::Down).await?,
-                        KeyCode::Up | KeyCode::Char('k') => app.dispatch(Action::Up).await?,
-                        KeyCode::Right | KeyCode::Char('l') => app.dispatch(Action::Right).await?,
-                        KeyCode::Char(to_enter) => app.dispatch(Action::Char(to_enter)).await?,
-                        keycode => app.dispatch(Action::Key(keycode)).await?,
-                    },
-                };
+            match event::read()? {
+                Event::Key(key) => {
+                    let context = KeyContext::from_focus(app.focus);
+
+                    if let Some(action) = app.keymap.action_for(context, key) {
+                        app.dispatch(action).await?;
+                    }
+
+                    if let Some(text) = app.pager.take() {
+                        if let Err(e) = suspended(terminal, || viewer::page(&text))? {
+                            app.notice = Some(format!("Pager failed: {}", e));
+                        }
+                    }
+
+                    app.persist();
+
+                    if app.should_quit {
+                        return Ok(());
+                    }
+                }
+                Event::Mouse(mouse) => {
+                    app.handle_mouse(mouse);
+                    app.persist();
+                }
+                _ => {}
             }
         }
 
@@ -137,3 +173,20 @@ async fn run_app<B: Backend>(
         }
     }
 }
+
+/// Gives the terminal back to the user while `f` runs, e.g. for a pager
+fn suspended<B: Backend, T>(
+    terminal: &mut Terminal<B>,
+    f: impl FnOnce() -> T,
+) -> anyhow::Result<T> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+
+    let result = f();
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    terminal.clear()?;
+
+    Ok(result)
+}
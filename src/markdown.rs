@@ -0,0 +1,132 @@
+/// A fenced code block from a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// The fence info string, e.g. `rust` or `python title="main.py"`
+    pub info: String,
+    pub code: String,
+}
+
+impl CodeBlock {
+    /// First word of the info string
+    pub fn language(&self) -> Option<&str> {
+        self.info
+            .split_whitespace()
+            .next()
+            .map(|word| word.split(':').next().unwrap_or(word))
+            .filter(|lang| !lang.is_empty())
+    }
+
+    /// A file name given in the info string (`rust:src/main.rs`, `title="main.py"`)
+    /// or in a comment on the first line (`// src/main.rs`, `# app.py`)
+    pub fn filename_hint(&self) -> Option<String> {
+        let from_info = self.info.split_whitespace().find_map(|word| {
+            let value = match word.split_once('=') {
+                Some((key, value)) if ["title", "file", "filename", "name"].contains(&key) => value,
+                Some(_) => return None,
+                None => word.split_once(':').map(|(_, path)| path).unwrap_or(word),
+            };
+            let value = value.trim_matches(|c| c == '"' || c == '\'');
+
+            looks_like_path(value).then(|| value.to_string())
+        });
+
+        let from_comment = || {
+            let first = self.code.lines().next()?.trim();
+            let comment = ["//", "#", "--", "/*", "<!--", ";"]
+                .iter()
+                .find_map(|marker| first.strip_prefix(marker))?;
+            let comment = comment
+                .trim()
+                .trim_end_matches("*/")
+                .trim_end_matches("-->")
+                .trim();
+            let comment = comment
+                .strip_prefix("file:")
+                .or_else(|| comment.strip_prefix("filename:"))
+                .unwrap_or(comment)
+                .trim();
+
+            looks_like_path(comment).then(|| comment.to_string())
+        };
+
+        from_info.or_else(from_comment)
+    }
+
+    /// File extension for the language, `txt` when unknown
+    pub fn extension(&self) -> &'static str {
+        match self.language().map(|l| l.to_lowercase()).as_deref() {
+            Some("rust" | "rs") => "rs",
+            Some("python" | "py") => "py",
+            Some("javascript" | "js" | "jsx") => "js",
+            Some("typescript" | "ts" | "tsx") => "ts",
+            Some("sh" | "bash" | "shell" | "zsh" | "console") => "sh",
+            Some("go" | "golang") => "go",
+            Some("c") => "c",
+            Some("cpp" | "c++" | "cc") => "cpp",
+            Some("java") => "java",
+            Some("ruby" | "rb") => "rb",
+            Some("html") => "html",
+            Some("css") => "css",
+            Some("json") => "json",
+            Some("yaml" | "yml") => "yaml",
+            Some("toml") => "toml",
+            Some("sql") => "sql",
+            Some("markdown" | "md") => "md",
+            Some("diff" | "patch") => "diff",
+            _ => "txt",
+        }
+    }
+}
+
+/// A relative or absolute file path with an extension, no spaces
+fn looks_like_path(s: &str) -> bool {
+    !s.is_empty()
+        && !s.contains(char::is_whitespace)
+        && s.rsplit('/').next().is_some_and(|name| {
+            name.contains('.') && !name.starts_with('.') && !name.ends_with('.')
+        })
+}
+
+/// Fenced code blocks in order, an unclosed last block runs to the end
+pub fn code_blocks(content: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(String, Vec<&str>)> = None;
+
+    for line in content.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+
+        match (current.take(), fence) {
+            (None, Some(info)) => current = Some((info.trim().to_string(), Vec::new())),
+            (None, None) => {}
+            (Some((info, lines)), Some(_)) => blocks.push(CodeBlock {
+                info,
+                code: lines.join("\n"),
+            }),
+            (Some((info, mut lines)), None) => {
+                lines.push(line);
+                current = Some((info, lines));
+            }
+        }
+    }
+
+    if let Some((info, lines)) = current {
+        blocks.push(CodeBlock {
+            info,
+            code: lines.join("\n"),
+        });
+    }
+
+    blocks
+}
+
+/// `> ` in front of every line
+pub fn quote(content: &str) -> String {
+    content
+        .lines()
+        .map(|line| match line.is_empty() {
+            true => ">".to_string(),
+            false => format!("> {}", line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -1,10 +1,14 @@
 use std::fmt::Display;
+use std::path::PathBuf;
 use std::str::FromStr;
 
+use chrono::{DateTime, Utc};
+
+use crate::components::scroll::Scroll;
 use crate::components::stateful_list::StatefulList;
-use openai_rust::chat::Message as OpenAIMessage;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 pub type ID = String;
 
@@ -19,10 +23,13 @@ fn random_id(length: usize) -> ID {
 
 // ---- Role
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Role {
     User,
     Assistant,
+    /// Result of a tool call, answers `Message::tool_call_id`
+    Tool,
 }
 
 impl FromStr for Role {
@@ -32,6 +39,7 @@ impl FromStr for Role {
         match s {
             "user" => Ok(Role::User),
             "assistant" => Ok(Role::Assistant),
+            "tool" => Ok(Role::Tool),
             _ => Err(anyhow::anyhow!("Invalid role")),
         }
     }
@@ -42,6 +50,7 @@ impl Display for Role {
         let str = match self {
             Role::User => "user".to_string(),
             Role::Assistant => "assistant".to_string(),
+            Role::Tool => "tool".to_string(),
         };
 
         write!(f, "{}", str)
@@ -50,11 +59,42 @@ impl Display for Role {
 
 // ---- Chat
 
-#[derive(Debug, Clone)]
+/// Where the chat title comes from, generated titles never replace a user one
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleSource {
+    #[default]
+    Default,
+    Generated,
+    User,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chat {
     pub id: ID,
     pub title: String,
+    #[serde(default)]
+    pub title_source: TitleSource,
     pub messages: StatefulList<Message>,
+    #[serde(skip)]
+    pub scroll: Scroll,
+    #[serde(default)]
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    /// Sent as the system message ahead of the conversation
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// ID of the conversation it was imported from
+    #[serde(default)]
+    pub source_id: Option<String>,
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    /// Last activity, bumped whenever a message is appended
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
 }
 
 impl Chat {
@@ -62,43 +102,217 @@ impl Chat {
         Self {
             id: random_id(7),
             title: title.to_string(),
+            title_source: TitleSource::Default,
             messages: StatefulList::with_items(vec![]),
+            scroll: Scroll::default(),
+            folder: None,
+            tags: vec![],
+            pinned: false,
+            system_prompt: None,
+            source_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
         }
     }
 
+    /// Drops the content of attached files before saving, see `Attachment::unload`
+    pub fn unload_attachments(&mut self) {
+        self.messages
+            .items
+            .iter_mut()
+            .flat_map(|m| m.attachments.iter_mut())
+            .for_each(Attachment::unload);
+    }
+
     pub fn append_message(&mut self, message: Message) {
         self.messages.items.push(message);
+        self.updated_at = Utc::now();
+    }
+
+    /// First line of the last message, used as a preview in the chats list
+    pub fn preview(&self) -> Option<&str> {
+        self.messages
+            .items
+            .last()
+            .and_then(|m| m.content.lines().find(|l| !l.trim().is_empty()))
+            .map(|l| l.trim())
     }
 
     pub fn remove_last_message(&mut self) {
         self.messages.items.pop();
     }
 
+    /// The call a tool message answers
+    pub fn tool_call(&self, id: &str) -> Option<&ToolCall> {
+        self.messages
+            .items
+            .iter()
+            .flat_map(|m| &m.tool_calls)
+            .find(|call| call.id == id)
+    }
+
     pub fn with_messages(title: &str, messages: Vec<Message>) -> Self {
         Self {
             id: random_id(7),
             title: title.to_string(),
+            title_source: TitleSource::Default,
             messages: StatefulList::with_items(messages),
+            scroll: Scroll::default(),
+            folder: None,
+            tags: vec![],
+            pinned: false,
+            system_prompt: None,
+            source_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
         }
     }
+
+    pub fn set_title(&mut self, title: &str, source: TitleSource) {
+        self.title = title.to_string();
+        self.title_source = source;
+    }
+
+    /// True once the first exchange happened and nobody named the chat yet
+    pub fn needs_title(&self) -> bool {
+        self.title_source == TitleSource::Default
+            && self
+                .messages
+                .items
+                .iter()
+                .any(|m| matches!(m.role, Role::Assistant))
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    /// Parses a comma/space separated list, e.g. `work, #rust`
+    pub fn set_tags(&mut self, tags: &str) {
+        self.tags = tags
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .map(|t| t.trim_start_matches('#').to_lowercase())
+            .filter(|t| !t.is_empty())
+            .fold(Vec::new(), |mut acc, t| {
+                if !acc.contains(&t) {
+                    acc.push(t);
+                }
+                acc
+            });
+    }
+
+    pub fn move_to_folder(&mut self, folder: &str) {
+        let folder = folder.trim();
+
+        self.folder = match folder.is_empty() {
+            true => None,
+            false => Some(folder.to_string()),
+        };
+    }
 }
 
 // ------ Message
 
-#[derive(Clone, Debug)]
-pub struct Message {
-    pub id: ID,
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentKind {
+    #[default]
+    File,
+    /// Output of a `!command`
+    Command,
+    /// PNG or JPEG, base64 encoded in the content
+    Image,
+}
+
+/// What is shown for an image instead of its data
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImageInfo {
+    pub mime: String,
+    pub width: usize,
+    pub height: usize,
+    /// Bytes of the file
+    pub size: usize,
+}
+
+/// A function the model asked to call
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    /// JSON object, as sent by the model
+    pub arguments: String,
+}
+
+/// A file or command output sent along with a message
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Attachment {
+    #[serde(default)]
+    pub kind: AttachmentKind,
+    /// Path as written after the `@`, or the command
+    pub name: String,
+    /// Absolute path of a file, to read it again once unloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Empty for a file that is not loaded, see `unload`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub content: String,
-    pub role: Role,
+    /// Bytes of the file or output
+    #[serde(default)]
+    pub size: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageInfo>,
+    /// Of the content as attached, to notice a file edited since
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u64>,
+    /// The file read again no longer matches `checksum`, see `attach::load`
+    #[serde(skip)]
+    pub changed: bool,
 }
 
-impl From<Message> for OpenAIMessage {
-    fn from(value: Message) -> Self {
-        Self {
-            content: value.content,
-            role: value.role.to_string(),
+impl Attachment {
+    /// Size of the attached file or output
+    pub fn size(&self) -> usize {
+        self.image.as_ref().map_or(self.size, |i| i.size)
+    }
+
+    /// Drops the content of a file or image, which is read again from `path`
+    /// before the next request. Keeps the saved chats small.
+    pub fn unload(&mut self) {
+        if self.path.is_some() {
+            self.content.clear();
         }
     }
+
+    pub fn is_loaded(&self) -> bool {
+        self.path.is_none() || !self.content.is_empty()
+    }
+}
+
+/// Part of a message sent to a vision model
+#[derive(Clone, Debug)]
+pub enum ContentPart {
+    Text(String),
+    /// `data:` URL of the image
+    Image(String),
+}
+
+/// Lines of a tool result shown in the conversation, the full text is in the viewer
+pub const TOOL_RESULT_LINES: usize = 5;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Message {
+    pub id: ID,
+    pub content: String,
+    pub role: Role,
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// Calls requested by an assistant message
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
 }
 
 impl Message {
@@ -107,9 +321,105 @@ impl Message {
             id: random_id(7),
             content: content.to_string(),
             role,
+            created_at: Utc::now(),
+            attachments: vec![],
+            tool_calls: vec![],
+            tool_call_id: None,
+        }
+    }
+
+    /// The text, then the images, `None` without images
+    pub fn content_parts(&self) -> Option<Vec<ContentPart>> {
+        let images: Vec<ContentPart> = self
+            .attachments
+            .iter()
+            .filter(|a| !a.content.is_empty())
+            .filter_map(|a| {
+                let image = a.image.as_ref()?;
+                Some(ContentPart::Image(format!(
+                    "data:{};base64,{}",
+                    image.mime, a.content
+                )))
+            })
+            .collect();
+
+        if images.is_empty() {
+            return None;
+        }
+
+        Some(
+            std::iter::once(ContentPart::Text(self.api_content()))
+                .chain(images)
+                .collect(),
+        )
+    }
+
+    /// Result of the call `call_id`
+    pub fn tool(call_id: &str, content: &str) -> Self {
+        Self {
+            tool_call_id: Some(call_id.to_string()),
+            ..Self::new(Role::Tool, content)
         }
     }
 
+    /// Lines drawn in the conversation, and searched. Tool results are cut to
+    /// `TOOL_RESULT_LINES`.
+    pub fn shown_lines(&self) -> impl Iterator<Item = &str> {
+        let limit = match self.role {
+            Role::Tool => TOOL_RESULT_LINES,
+            _ => usize::MAX,
+        };
+
+        self.content.lines().take(limit)
+    }
+
+    /// Tool calls and their results, only understood by requests with tools
+    pub fn is_tool_traffic(&self) -> bool {
+        matches!(self.role, Role::Tool) || !self.tool_calls.is_empty()
+    }
+
+    /// The content followed by the text attachments, each between `<file>` or
+    /// `<output>` tags. Images are left out.
+    pub fn api_content(&self) -> String {
+        let mut content = self.content.clone();
+
+        for attachment in &self.attachments {
+            // the model answered the content as it was
+            let note = match attachment.changed {
+                true => " note=\"changed on disk since this message was sent\"",
+                false => "",
+            };
+
+            let (open, close) = match attachment.kind {
+                AttachmentKind::Image if attachment.changed => {
+                    content.push_str(&format!(
+                        "\n\n[image {} changed on disk since this message was sent]",
+                        attachment.name
+                    ));
+                    continue;
+                }
+                AttachmentKind::Image => continue,
+                AttachmentKind::File => (
+                    format!("<file path=\"{}\"{}>", attachment.name, note),
+                    "</file>",
+                ),
+                AttachmentKind::Command => (
+                    format!("<output command=\"{}\">", attachment.name),
+                    "</output>",
+                ),
+            };
+
+            content.push_str(&format!(
+                "\n\n{}\n{}\n{}",
+                open,
+                attachment.content.trim_end_matches('\n'),
+                close
+            ));
+        }
+
+        content
+    }
+
     pub fn assistant(content: &str) -> Self {
         Self::new(Role::Assistant, content)
     }
@@ -0,0 +1,71 @@
+use ratatui::layout::{Margin, Rect};
+
+use crate::state::Section;
+
+/// Screen areas from the last `ui::render`, used to hit-test mouse events
+#[derive(Debug, Default, Clone)]
+pub struct Areas {
+    pub chats: Rect,
+    pub messages: Rect,
+    pub input: Rect,
+    /// Visible sidebar rows as (area, row index)
+    pub chat_rows: Vec<(Rect, usize)>,
+    /// Visible messages as (area, message index)
+    pub message_rows: Vec<(Rect, usize)>,
+}
+
+impl Areas {
+    pub fn section_at(&self, column: u16, row: u16) -> Option<Section> {
+        [
+            (self.chats, Section::Chats),
+            (self.messages, Section::Messages),
+            (self.input, Section::Input),
+        ]
+        .into_iter()
+        .find(|(area, _)| contains(*area, column, row))
+        .map(|(_, section)| section)
+    }
+
+    pub fn chat_row_at(&self, column: u16, row: u16) -> Option<usize> {
+        row_at(&self.chat_rows, column, row)
+    }
+
+    pub fn message_at(&self, column: u16, row: u16) -> Option<usize> {
+        row_at(&self.message_rows, column, row)
+    }
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    (area.x..area.x + area.width).contains(&column) && (area.y..area.y + area.height).contains(&row)
+}
+
+fn row_at(rows: &[(Rect, usize)], column: u16, row: u16) -> Option<usize> {
+    rows.iter()
+        .find(|(area, _)| contains(*area, column, row))
+        .map(|(_, idx)| *idx)
+}
+
+/// Lays out list items of `heights` inside the borders of `area`, starting at
+/// `offset` the same way `List` does
+pub fn list_rows(area: Rect, offset: usize, heights: &[u16]) -> Vec<(Rect, usize)> {
+    let inner = area.inner(&Margin {
+        horizontal: 1,
+        vertical: 1,
+    });
+    let bottom = inner.y + inner.height;
+    let mut y = inner.y;
+    let mut rows = Vec::new();
+
+    for (idx, height) in heights.iter().enumerate().skip(offset) {
+        // only an item taller than the whole list is drawn partially
+        if y.saturating_add(*height) > bottom && !rows.is_empty() {
+            break;
+        }
+
+        let height = (*height).min(bottom - y);
+        rows.push((Rect::new(inner.x, y, inner.width, height), idx));
+        y += height;
+    }
+
+    rows
+}
@@ -1,16 +1,309 @@
-use crate::models::{Chat, Message};
-use openai_rust::{chat, Client as OpenAIClient};
+use std::path::Path;
 
-pub async fn send_message(chat: Chat) -> anyhow::Result<Option<Message>> {
-    let client = OpenAIClient::new(&std::env::var("OPENAI_API_KEY").unwrap());
+use crate::attach;
+use crate::config::{AutoTitleConfig, TranscriptionConfig};
+use crate::models::{Chat, ContentPart, Message, Role, ToolCall};
+use crate::tools;
+use serde::Deserialize;
+use serde_json::{json, Value};
 
-    let messages: Vec<chat::Message> = chat.messages.items.into_iter().map(|m| m.into()).collect();
-    let args = chat::ChatArguments::new("gpt-3.5-turbo", messages);
+const CHAT_COMPLETIONS_URL: &str = "https://api.openai.com/v1/chat/completions";
 
-    let res = client.create_chat(args).await?;
+fn api_key() -> anyhow::Result<String> {
+    std::env::var("OPENAI_API_KEY").map_err(|_| anyhow::anyhow!("OPENAI_API_KEY is not set"))
+}
+
+/// Posts `body` to the chat completions API, failing on an error status
+async fn post_completion(body: &Value) -> anyhow::Result<reqwest::Response> {
+    let res = reqwest::Client::new()
+        .post(CHAT_COMPLETIONS_URL)
+        .bearer_auth(api_key()?)
+        .json(body)
+        .send()
+        .await?;
+
+    check_status(res, "OpenAI API error").await
+}
+
+/// Turns an error status into an error with the message from the body
+async fn check_status(res: reqwest::Response, context: &str) -> anyhow::Result<reqwest::Response> {
+    if res.status().is_success() {
+        return Ok(res);
+    }
+
+    let status = res.status();
+    let error: Value = res.json().await.unwrap_or_default();
+    let message = error["error"]["message"].as_str().unwrap_or("no details");
+
+    anyhow::bail!("{} ({}): {}", context, status, message)
+}
+
+#[derive(Deserialize)]
+struct CompletionResponse {
+    choices: Vec<CompletionChoice>,
+}
+
+#[derive(Deserialize)]
+struct CompletionChoice {
+    message: CompletionMessage,
+}
+
+#[derive(Deserialize)]
+struct CompletionMessage {
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<CompletionToolCall>,
+}
+
+#[derive(Deserialize)]
+struct StreamChunk {
+    choices: Vec<StreamChoice>,
+}
+
+#[derive(Deserialize)]
+struct StreamChoice {
+    delta: StreamDelta,
+}
+
+#[derive(Deserialize)]
+struct StreamDelta {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CompletionToolCall {
+    id: String,
+    function: CompletionFunction,
+}
+
+#[derive(Deserialize)]
+struct CompletionFunction {
+    name: String,
+    arguments: String,
+}
+
+/// `chat` in the request format, with its tool calls and results
+fn request_messages(chat: &Chat) -> Vec<Value> {
+    let system = chat
+        .system_prompt
+        .as_ref()
+        .map(|prompt| json!({ "role": "system", "content": prompt }));
+
+    let messages = chat.messages.items.iter().map(|m| match m.role {
+        Role::Tool => json!({
+            "role": "tool",
+            "tool_call_id": m.tool_call_id,
+            "content": m.content,
+        }),
+        Role::Assistant if !m.tool_calls.is_empty() => json!({
+            "role": "assistant",
+            "content": Some(&m.content).filter(|c| !c.is_empty()),
+            "tool_calls": m.tool_calls.iter().map(|call| json!({
+                "id": call.id,
+                "type": "function",
+                "function": { "name": call.name, "arguments": call.arguments },
+            })).collect::<Vec<_>>(),
+        }),
+        _ => json!({ "role": m.role.to_string(), "content": request_content(m) }),
+    });
 
-    match res.choices.first().map(|c| c.clone().message.content) {
-        Some(content) => Ok(Some(Message::assistant(&content))),
-        None => Ok(None),
+    system.into_iter().chain(messages).collect()
+}
+
+/// Plain text, or text and image parts when the message has images
+fn request_content(message: &Message) -> Value {
+    let Some(parts) = message.content_parts() else {
+        return json!(message.api_content());
+    };
+
+    parts
+        .into_iter()
+        .map(|part| match part {
+            ContentPart::Text(text) => json!({ "type": "text", "text": text }),
+            ContentPart::Image(url) => json!({ "type": "image_url", "image_url": { "url": url } }),
+        })
+        .collect()
+}
+
+/// Sends `chat` through the chat completions API, offering the local tools when
+/// `with_tools` is set. The answer may be tool calls instead of content.
+pub async fn send_message(
+    chat: Chat,
+    model: &str,
+    with_tools: bool,
+) -> anyhow::Result<Option<Message>> {
+    let mut body = json!({
+        "model": model,
+        "messages": request_messages(&chat),
+    });
+
+    if with_tools {
+        body["tools"] = tools::definitions();
     }
+
+    let res: CompletionResponse = post_completion(&body).await?.json().await?;
+
+    let Some(choice) = res.choices.into_iter().next() else {
+        return Ok(None);
+    };
+
+    let mut message = Message::assistant(&choice.message.content.unwrap_or_default());
+    message.tool_calls = choice
+        .message
+        .tool_calls
+        .into_iter()
+        .map(|call| ToolCall {
+            id: call.id,
+            name: call.function.name,
+            arguments: call.function.arguments,
+        })
+        .collect();
+
+    Ok(Some(message))
+}
+
+/// Like `send_message` without tools, but calls `on_chunk` as the answer comes
+/// in, returns the full answer
+pub async fn stream_message(
+    mut chat: Chat,
+    model: &str,
+    mut on_chunk: impl FnMut(&str),
+) -> anyhow::Result<String> {
+    chat.messages.items.retain(|m| !m.is_tool_traffic());
+
+    let body = json!({
+        "model": model,
+        "messages": request_messages(&chat),
+        "stream": true,
+    });
+
+    let mut res = post_completion(&body).await?;
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut answer = String::new();
+
+    // server-sent events, one `data: {chunk}` line each
+    while let Some(bytes) = res.chunk().await? {
+        buffer.extend_from_slice(&bytes);
+
+        while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+
+            let Some(data) = line.trim().strip_prefix("data:").map(str::trim) else {
+                continue;
+            };
+
+            if data == "[DONE]" {
+                return Ok(answer);
+            }
+
+            let chunk: StreamChunk = serde_json::from_str(data)?;
+
+            if let Some(content) = chunk.choices.first().and_then(|c| c.delta.content.as_ref()) {
+                on_chunk(content);
+                answer.push_str(content);
+            }
+        }
+    }
+
+    Ok(answer)
+}
+
+/// Asks the model for a short title summarizing the conversation
+pub async fn generate_title(
+    mut chat: Chat,
+    config: AutoTitleConfig,
+) -> anyhow::Result<Option<String>> {
+    chat.messages.items.retain(|m| !m.is_tool_traffic());
+    chat.append_message(Message::user(&config.prompt));
+
+    // the text only, attached files and images would make titles expensive
+    let messages: Vec<Value> = chat
+        .messages
+        .items
+        .iter()
+        .map(|m| json!({ "role": m.role.to_string(), "content": m.content }))
+        .collect();
+
+    let body = json!({
+        "model": config.model,
+        "messages": messages,
+    });
+
+    let res: CompletionResponse = post_completion(&body).await?.json().await?;
+
+    let title = res
+        .choices
+        .first()
+        .and_then(|c| c.message.content.as_deref())
+        .and_then(|content| content.lines().find(|l| !l.trim().is_empty()))
+        .map(|l| {
+            l.trim()
+                .trim_matches(|c: char| c == '"' || c == '\'' || c == '.')
+                .chars()
+                .take(40)
+                .collect::<String>()
+        })
+        .filter(|t| !t.is_empty());
+
+    Ok(title)
+}
+
+#[derive(Deserialize)]
+struct TranscriptionResponse {
+    text: String,
+}
+
+/// Sends an audio file to the transcription endpoint. The API key is optional
+/// so that local servers work without one.
+pub async fn transcribe(path: &str, config: &TranscriptionConfig) -> anyhow::Result<String> {
+    let mime = attach::audio_mime(path)
+        .ok_or_else(|| anyhow::anyhow!("{} is not a WAV, MP3 or M4A file", path))?;
+
+    let size = std::fs::metadata(path)
+        .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?
+        .len() as usize;
+
+    if size > config.max_size {
+        anyhow::bail!(
+            "{} is {}, the limit is {}",
+            path,
+            attach::human_size(size),
+            attach::human_size(config.max_size)
+        );
+    }
+
+    let audio = tokio::fs::read(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let file = reqwest::multipart::Part::bytes(audio)
+        .file_name(file_name)
+        .mime_str(mime)?;
+    let form = reqwest::multipart::Form::new()
+        .text("model", config.model.clone())
+        .part("file", file);
+
+    let mut request = reqwest::Client::new().post(&config.url).multipart(form);
+
+    let is_openai =
+        reqwest::Url::parse(&config.url).is_ok_and(|url| url.host_str() == Some("api.openai.com"));
+    let api_key = match &config.api_key {
+        Some(key) => Some(key.clone()),
+        None if is_openai => api_key().ok(),
+        None => None,
+    };
+
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+
+    let res = check_status(request.send().await?, "transcription failed").await?;
+    let res: TranscriptionResponse = res.json().await?;
+
+    Ok(res.text.trim().to_string())
 }
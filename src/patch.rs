@@ -0,0 +1,490 @@
+use std::fs;
+
+use crate::markdown;
+use crate::utils::checked_path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    pub header: String,
+    /// 1-based line in the original file, used to pick between several matches
+    pub old_start: usize,
+    pub lines: Vec<HunkLine>,
+    pub accepted: bool,
+}
+
+impl Hunk {
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(l) | HunkLine::Remove(l) => Some(l.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    fn new_lines(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(l) | HunkLine::Add(l) => Some(l.clone()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Changes to one file, paths are `None` for `/dev/null`
+#[derive(Debug, Clone)]
+pub struct FilePatch {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or("")
+    }
+
+    pub fn is_deletion(&self) -> bool {
+        self.new_path.is_none()
+    }
+}
+
+/// `a/src/main.rs\t2024-01-01` -> `src/main.rs`
+fn parse_path(s: &str) -> Option<String> {
+    let path = s.split('\t').next().unwrap_or(s).trim();
+
+    if path == "/dev/null" {
+        return None;
+    }
+
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+
+    Some(path.to_string())
+}
+
+/// `@@ -12,7 +12,8 @@ fn main` -> 12
+fn parse_old_start(header: &str) -> Option<usize> {
+    let old = header.strip_prefix("@@ -")?.split_whitespace().next()?;
+    old.split(',').next()?.parse().ok()
+}
+
+/// Unified diffs from the ```diff / ```patch blocks of a message, or from the
+/// whole message when it has none. Hunk line counts are ignored since models
+/// often get them wrong.
+pub fn parse(content: &str) -> Vec<FilePatch> {
+    let blocks: Vec<String> = markdown::code_blocks(content)
+        .into_iter()
+        .filter(|b| matches!(b.language(), Some("diff" | "patch")))
+        .map(|b| b.code)
+        .collect();
+
+    let sources = match blocks.is_empty() {
+        true => vec![content.to_string()],
+        false => blocks,
+    };
+
+    sources.iter().flat_map(|s| parse_diff(s)).collect()
+}
+
+fn parse_diff(diff: &str) -> Vec<FilePatch> {
+    let lines: Vec<&str> = diff.lines().collect();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if let (Some(old), Some(new)) = (
+            line.strip_prefix("--- "),
+            lines.get(i + 1).and_then(|l| l.strip_prefix("+++ ")),
+        ) {
+            files.push(FilePatch {
+                old_path: parse_path(old),
+                new_path: parse_path(new),
+                hunks: Vec::new(),
+            });
+            i += 2;
+            continue;
+        }
+
+        if line.starts_with("@@") {
+            if let Some(file) = files.last_mut() {
+                file.hunks.push(Hunk {
+                    header: line.to_string(),
+                    old_start: parse_old_start(line).unwrap_or(1),
+                    lines: Vec::new(),
+                    accepted: true,
+                });
+            }
+            i += 1;
+            continue;
+        }
+
+        let hunk = files.last_mut().and_then(|f| f.hunks.last_mut());
+
+        if let Some(hunk) = hunk {
+            let hunk_line = match line.chars().next() {
+                Some(' ') => Some(HunkLine::Context(line[1..].to_string())),
+                // blank context lines often lose their leading space
+                None => Some(HunkLine::Context(String::new())),
+                Some('-') => Some(HunkLine::Remove(line[1..].to_string())),
+                Some('+') => Some(HunkLine::Add(line[1..].to_string())),
+                _ => None,
+            };
+
+            if let Some(hunk_line) = hunk_line {
+                hunk.lines.push(hunk_line);
+            }
+        }
+
+        i += 1;
+    }
+
+    for hunk in files.iter_mut().flat_map(|f| f.hunks.iter_mut()) {
+        while hunk.lines.last() == Some(&HunkLine::Context(String::new())) {
+            hunk.lines.pop();
+        }
+    }
+
+    files.retain(|f| !f.hunks.is_empty());
+    files
+}
+
+/// Parsed patch being reviewed hunk by hunk
+#[derive(Debug, Clone)]
+pub struct PatchReview {
+    pub files: Vec<FilePatch>,
+    /// Index into the hunks of all files, in order
+    pub selected: usize,
+}
+
+impl PatchReview {
+    pub fn new(files: Vec<FilePatch>) -> Self {
+        Self { files, selected: 0 }
+    }
+
+    pub fn hunk_count(&self) -> usize {
+        self.files.iter().map(|f| f.hunks.len()).sum()
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1).min(self.hunk_count().saturating_sub(1));
+    }
+
+    pub fn prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn selected_hunk_mut(&mut self) -> Option<&mut Hunk> {
+        self.files
+            .iter_mut()
+            .flat_map(|f| f.hunks.iter_mut())
+            .nth(self.selected)
+    }
+
+    pub fn toggle(&mut self) {
+        if let Some(hunk) = self.selected_hunk_mut() {
+            hunk.accepted = !hunk.accepted;
+        }
+    }
+
+    pub fn set_accepted(&mut self, accepted: bool) {
+        if let Some(hunk) = self.selected_hunk_mut() {
+            hunk.accepted = accepted;
+        }
+    }
+
+    /// Applies the accepted hunks, relative to the current directory
+    pub fn apply(&self) -> ApplyReport {
+        let mut report = ApplyReport::default();
+
+        for file in &self.files {
+            if !file.hunks.iter().any(|h| h.accepted) {
+                continue;
+            }
+
+            match apply_file(file) {
+                Ok((applied, conflicts)) => {
+                    report.applied += applied;
+                    report.files += (applied > 0) as usize;
+                    report.conflicts.extend(
+                        conflicts
+                            .into_iter()
+                            .map(|n| format!("{} hunk {}", file.path(), n)),
+                    );
+                }
+                Err(e) => report.conflicts.push(format!("{}: {}", file.path(), e)),
+            }
+        }
+
+        report
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ApplyReport {
+    pub applied: usize,
+    pub files: usize,
+    pub conflicts: Vec<String>,
+}
+
+impl ApplyReport {
+    pub fn summary(&self) -> String {
+        let mut summary = format!("Applied {} hunk(s) to {} file(s)", self.applied, self.files);
+
+        if !self.conflicts.is_empty() {
+            summary.push_str(&format!(
+                ", {} conflict(s): {}",
+                self.conflicts.len(),
+                self.conflicts.join(", ")
+            ));
+        }
+
+        summary
+    }
+}
+
+/// Position of `old` in `lines` at or after `from`, closest to `expected`.
+/// Trailing whitespace is ignored.
+fn find_hunk(lines: &[String], old: &[&str], from: usize, expected: usize) -> Option<usize> {
+    if old.is_empty() {
+        return Some(expected.max(from).min(lines.len()));
+    }
+
+    if old.len() > lines.len() {
+        return None;
+    }
+
+    (from..=lines.len() - old.len())
+        .filter(|&start| {
+            lines[start..start + old.len()]
+                .iter()
+                .zip(old)
+                .all(|(a, b)| a.trim_end() == b.trim_end())
+        })
+        .min_by_key(|&start| start.abs_diff(expected))
+}
+
+/// The accepted hunks of `file` applied to `original`, with the number of
+/// applied hunks and the (1-based) hunks that did not match. Line endings
+/// follow the original, CRLF or LF.
+fn patch_text(original: &str, file: &FilePatch) -> (String, usize, Vec<usize>) {
+    let mut lines: Vec<String> = original.lines().map(|l| l.to_string()).collect();
+    let mut applied = 0;
+    let mut conflicts = Vec::new();
+    // hunks apply in order, each one after the previous
+    let mut from = 0;
+    let mut offset: isize = 0;
+
+    for (n, hunk) in file.hunks.iter().enumerate() {
+        if !hunk.accepted {
+            continue;
+        }
+
+        let old = hunk.old_lines();
+        let expected = (hunk.old_start.saturating_sub(1) as isize + offset).max(0) as usize;
+
+        match find_hunk(&lines, &old, from, expected) {
+            Some(start) => {
+                let new = hunk.new_lines();
+                from = start + new.len();
+                offset += new.len() as isize - old.len() as isize;
+                lines.splice(start..start + old.len(), new);
+                applied += 1;
+            }
+            None => conflicts.push(n + 1),
+        }
+    }
+
+    let newline = match original.contains("\r\n") {
+        true => "\r\n",
+        false => "\n",
+    };
+
+    let mut content = lines.join(newline);
+    if (original.ends_with('\n') || original.is_empty()) && !lines.is_empty() {
+        content.push_str(newline);
+    }
+
+    (content, applied, conflicts)
+}
+
+/// Returns the number of applied hunks and the (1-based) hunks that did not match.
+/// A rename reads the old path and writes the new one.
+fn apply_file(file: &FilePatch) -> anyhow::Result<(usize, Vec<usize>)> {
+    let path = checked_path(file.path())?;
+    let source = file.old_path.as_deref().map(checked_path).transpose()?;
+    let renamed = source.as_ref().is_some_and(|source| *source != path);
+
+    let original = match &source {
+        Some(_) if renamed && path.exists() => anyhow::bail!("{} already exists", path.display()),
+        Some(source) => fs::read_to_string(source).map_err(|e| anyhow::anyhow!("{}", e))?,
+        None if path.exists() => anyhow::bail!("already exists"),
+        None => String::new(),
+    };
+
+    let (content, applied, conflicts) = patch_text(&original, file);
+
+    if applied == 0 {
+        return Ok((applied, conflicts));
+    }
+
+    if file.is_deletion() && content.is_empty() && conflicts.is_empty() {
+        fs::remove_file(&path)?;
+        return Ok((applied, conflicts));
+    }
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(&path, content)?;
+
+    if let Some(source) = source.filter(|_| renamed) {
+        fs::remove_file(source)?;
+    }
+
+    Ok((applied, conflicts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    fn file(diff: &str) -> FilePatch {
+        let mut files = parse_diff(diff);
+        assert_eq!(files.len(), 1);
+        files.remove(0)
+    }
+
+    #[test]
+    fn parses_files_and_hunks() {
+        let files = parse_diff(
+            "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1,2 +1,2 @@\n fn a() {\n-    1\n+    2\n\
+             --- /dev/null\n+++ b/new.txt\n@@ -0,0 +1 @@\n+hello\n",
+        );
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path(), "src/a.rs");
+        assert_eq!(
+            files[0].hunks[0].lines,
+            vec![
+                HunkLine::Context("fn a() {".to_string()),
+                HunkLine::Remove("    1".to_string()),
+                HunkLine::Add("    2".to_string()),
+            ]
+        );
+        assert_eq!(files[1].old_path, None);
+        assert_eq!(files[1].hunks[0].old_start, 0);
+    }
+
+    #[test]
+    fn removed_dashes_inside_a_hunk_are_not_a_header() {
+        let file = file("--- a/notes.md\n+++ b/notes.md\n@@ -1,2 +1,1 @@\n--- foo\n bar\n");
+
+        assert_eq!(
+            file.hunks[0].lines,
+            vec![
+                HunkLine::Remove("-- foo".to_string()),
+                HunkLine::Context("bar".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn hunk_longer_than_the_file_does_not_match() {
+        assert_eq!(find_hunk(&lines("a\nb"), &["a", "b", "c"], 0, 0), None);
+        assert_eq!(find_hunk(&[], &["a"], 0, 0), None);
+    }
+
+    #[test]
+    fn insertion_into_an_empty_file() {
+        assert_eq!(find_hunk(&[], &[], 0, 5), Some(0));
+
+        let file = file("--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1,2 @@\n+one\n+two\n");
+        assert_eq!(patch_text("", &file), ("one\ntwo\n".to_string(), 1, vec![]));
+    }
+
+    #[test]
+    fn match_closest_to_the_expected_line() {
+        let text = lines("x\ny\nx\ny\nx\ny");
+
+        assert_eq!(find_hunk(&text, &["x", "y"], 0, 3), Some(2));
+        assert_eq!(find_hunk(&text, &["x", "y"], 3, 0), Some(4));
+    }
+
+    #[test]
+    fn hunks_with_wrong_line_numbers_still_apply() {
+        let file =
+            file("--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n c\n-d\n+D\n@@ -2,2 +2,3 @@\n g\n+G\n h\n");
+
+        assert_eq!(
+            patch_text("a\nb\nc\nd\ne\nf\ng\nh\n", &file),
+            ("a\nb\nc\nD\ne\nf\ng\nG\nh\n".to_string(), 2, vec![])
+        );
+    }
+
+    #[test]
+    fn conflicting_hunks_are_reported() {
+        let file = file("--- a/f\n+++ b/f\n@@ -1 +1 @@\n-missing\n+x\n@@ -2 +2 @@\n-b\n+B\n");
+
+        assert_eq!(
+            patch_text("a\nb\n", &file),
+            ("a\nB\n".to_string(), 1, vec![1])
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_are_kept() {
+        let file = file("--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n a\n-b\n+B\n");
+
+        assert_eq!(
+            patch_text("a\r\nb\r\n", &file),
+            ("a\r\nB\r\n".to_string(), 1, vec![])
+        );
+    }
+
+    #[test]
+    fn renames_read_the_old_path_and_write_the_new_one() {
+        let dir = Path::new("target/patch-rename-test");
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("old.txt"), "a\nb\n").unwrap();
+
+        let rename = file(
+            "--- a/target/patch-rename-test/old.txt\n\
+             +++ b/target/patch-rename-test/new.txt\n\
+             @@ -1,2 +1,2 @@\n a\n-b\n+c\n",
+        );
+
+        assert_eq!(apply_file(&rename).unwrap(), (1, vec![]));
+        assert_eq!(fs::read_to_string(dir.join("new.txt")).unwrap(), "a\nc\n");
+        assert!(!dir.join("old.txt").exists());
+
+        // the old path is gone now
+        assert!(apply_file(&rename).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
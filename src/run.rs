@@ -0,0 +1,315 @@
+use std::os::unix::process::CommandExt;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use regex::Regex;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::RunConfig;
+use crate::markdown::CodeBlock;
+use crate::models::ID;
+use crate::state::AppEvent;
+
+/// A shell code block waiting for confirmation
+#[derive(Debug, Clone)]
+pub struct PendingRun {
+    pub chat_id: ID,
+    pub script: String,
+}
+
+/// The commands of a shell block. `console` blocks only keep the `$ ` lines.
+pub fn script(block: &CodeBlock) -> anyhow::Result<String> {
+    let language = block.language().map(|l| l.to_lowercase());
+
+    let script = match language.as_deref() {
+        Some("console") => block
+            .code
+            .lines()
+            .filter_map(|l| l.strip_prefix("$ "))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None | Some("sh" | "bash" | "shell" | "zsh") => block.code.clone(),
+        Some(language) => anyhow::bail!("not a shell block ({})", language),
+    };
+
+    if script.trim().is_empty() {
+        anyhow::bail!("nothing to run");
+    }
+
+    Ok(script)
+}
+
+/// Checks every program of `script` against the allowlist. The script is only
+/// split on separators and pipes, so substitutions and redirections to files
+/// are refused unless everything is allowed.
+pub fn check_allowed(script: &str, allowlist: &[String]) -> anyhow::Result<()> {
+    if allowlist.is_empty() {
+        anyhow::bail!("running code is disabled, add programs to [run] allowlist in config.toml");
+    }
+
+    if allowlist.iter().any(|a| a == "*") {
+        return Ok(());
+    }
+
+    if script.contains('`') || script.contains("$(") || script.contains("<(") {
+        anyhow::bail!("command substitution needs `*` in the allowlist");
+    }
+
+    // `2>&1` only duplicates a descriptor, it is not a background job
+    let fd_duplication = Regex::new(r"\d*[<>]&(\d+|-)").expect("valid regex");
+    let script = fd_duplication.replace_all(script, " ");
+
+    if script.contains('>') {
+        anyhow::bail!("redirecting output needs `*` in the allowlist");
+    }
+
+    let programs: Vec<&str> = script
+        .split(['\n', ';', '|', '&'])
+        .map(str::trim)
+        .filter(|command| !command.is_empty() && !command.starts_with('#'))
+        .filter_map(|command| command.split_whitespace().next())
+        .collect();
+
+    // `PATH=.` or `LD_PRELOAD=` would change what an allowed program runs
+    if programs.iter().any(|program| program.contains('=')) {
+        anyhow::bail!("setting variables needs `*` in the allowlist");
+    }
+
+    // a program given with a path only passes when that path is allowed
+    let denied: Vec<&str> = programs
+        .into_iter()
+        .filter(|program| !allowlist.iter().any(|a| a == program))
+        .collect();
+
+    match denied.is_empty() {
+        true => Ok(()),
+        false => anyhow::bail!("not in the allowlist: {}", denied.join(", ")),
+    }
+}
+
+/// `sh -c script` in a process group of its own, so a timeout can kill
+/// everything it started
+fn shell(script: &str) -> Command {
+    let mut command = std::process::Command::new("sh");
+    command
+        .arg("-c")
+        .arg(script)
+        .stdin(Stdio::null())
+        .process_group(0);
+
+    let mut command = Command::from(command);
+    command.kill_on_drop(true);
+    command
+}
+
+/// Kills the process group led by `pid`
+fn kill_group(pid: Option<u32>) {
+    if let Some(pid) = pid {
+        // SAFETY: plain syscall, a group that is already gone only returns ESRCH
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+    }
+}
+
+/// Runs `script` with `sh -c` in the background, sending its output line by
+/// line, then its exit status, as events for `message_id`
+pub fn spawn(
+    run: PendingRun,
+    message_id: ID,
+    send: bool,
+    config: RunConfig,
+    events: UnboundedSender<AppEvent>,
+) {
+    tokio::spawn(async move {
+        let status = match execute(&run, &message_id, &config, &events).await {
+            Ok(status) => status,
+            Err(e) => format!("failed: {}", e),
+        };
+
+        let _ = events.send(AppEvent::RunFinished {
+            chat_id: run.chat_id,
+            message_id,
+            status,
+            send,
+        });
+    });
+}
+
+async fn execute(
+    run: &PendingRun,
+    message_id: &ID,
+    config: &RunConfig,
+    events: &UnboundedSender<AppEvent>,
+) -> anyhow::Result<String> {
+    let mut child = shell(&run.script)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let pid = child.id();
+
+    // shared between stdout and stderr
+    let budget = Arc::new(AtomicUsize::new(config.max_output));
+
+    let readers = [
+        child
+            .stdout
+            .take()
+            .map(|out| Box::new(out) as Box<dyn AsyncRead + Send + Unpin>),
+        child
+            .stderr
+            .take()
+            .map(|err| Box::new(err) as Box<dyn AsyncRead + Send + Unpin>),
+    ];
+
+    let mut tasks = Vec::new();
+
+    for reader in readers.into_iter().flatten() {
+        let events = events.clone();
+        let chat_id = run.chat_id.clone();
+        let message_id = message_id.clone();
+        let budget = budget.clone();
+
+        tasks.push(tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                let size = line.len() + 1;
+                let left = budget
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                        Some(left.saturating_sub(size))
+                    })
+                    .unwrap_or(0);
+
+                let text = match left {
+                    0 => continue,
+                    left if left < size => "[output truncated]\n".to_string(),
+                    _ => format!("{}\n", line),
+                };
+
+                let _ = events.send(AppEvent::RunOutput {
+                    chat_id: chat_id.clone(),
+                    message_id: message_id.clone(),
+                    text,
+                });
+            }
+        }));
+    }
+
+    let status = match tokio::time::timeout(Duration::from_secs(config.timeout), child.wait()).await
+    {
+        Ok(status) => status?.to_string(),
+        Err(_) => {
+            kill_group(pid);
+            child.wait().await?;
+            format!("killed after {}s", config.timeout)
+        }
+    };
+
+    // let the last lines arrive before the status, background processes may
+    // keep the pipes open though
+    for task in tasks {
+        let _ = tokio::time::timeout(Duration::from_secs(1), task).await;
+    }
+
+    Ok(status)
+}
+
+/// Runs `script` to completion, returning its output, stderr included, and its
+/// exit status
+pub async fn capture(script: &str, config: &RunConfig) -> anyhow::Result<(String, String)> {
+    let child = shell(&format!("{{ {}\n}} 2>&1", script))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let pid = child.id();
+
+    let output = match tokio::time::timeout(
+        Duration::from_secs(config.timeout),
+        child.wait_with_output(),
+    )
+    .await
+    {
+        Ok(output) => output?,
+        Err(_) => {
+            kill_group(pid);
+            anyhow::bail!("timed out after {}s", config.timeout);
+        }
+    };
+
+    Ok((
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        output.status.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allow(programs: &[&str]) -> Vec<String> {
+        programs.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn every_command_of_a_script_is_checked() {
+        let allowlist = allow(&["cargo", "grep", "echo"]);
+
+        assert!(check_allowed("cargo test 2>&1 | grep FAILED", &allowlist).is_ok());
+        assert!(check_allowed("cargo build && echo done; echo ok\n# comment", &allowlist).is_ok());
+
+        let denied = check_allowed("cargo build || rm -rf target & curl x", &allowlist);
+        assert_eq!(
+            denied.unwrap_err().to_string(),
+            "not in the allowlist: rm, curl"
+        );
+    }
+
+    #[test]
+    fn variables_and_other_paths_are_refused() {
+        let allowlist = allow(&["cargo", "echo", "/usr/bin/make"]);
+
+        for script in [
+            "PATH=. cargo build",
+            "LD_PRELOAD=./x.so echo hi",
+            "echo ok && RUST_LOG=debug cargo run",
+            "./cargo build",
+            "/tmp/x/cargo build",
+            "/usr/bin/cargo build",
+            "make",
+        ] {
+            assert!(check_allowed(script, &allowlist).is_err(), "{}", script);
+        }
+
+        assert!(check_allowed("/usr/bin/make && cargo build", &allowlist).is_ok());
+    }
+
+    #[test]
+    fn substitutions_and_redirections_need_everything_allowed() {
+        let allowlist = allow(&["echo", "cat", "diff"]);
+
+        for script in [
+            "echo `rm -rf ~`",
+            "echo $(rm -rf ~)",
+            "diff <(cat a) b",
+            "echo hi > ~/.bashrc",
+            "echo hi >> notes",
+            "echo hi &> log",
+            "echo hi >(cat)",
+        ] {
+            assert!(check_allowed(script, &allowlist).is_err(), "{}", script);
+            assert!(check_allowed(script, &allow(&["*"])).is_ok(), "{}", script);
+        }
+
+        assert!(check_allowed("cat < notes 2>&1 >&2", &allowlist).is_ok());
+    }
+
+    #[test]
+    fn an_empty_allowlist_disables_running() {
+        assert!(check_allowed("echo hi", &[]).is_err());
+    }
+}
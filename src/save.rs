@@ -0,0 +1,72 @@
+use std::fs;
+use std::path::PathBuf;
+
+use similar::TextDiff;
+
+use crate::markdown::CodeBlock;
+use crate::storage::expand_home;
+use crate::utils::checked_path;
+
+/// A code block about to be written, waiting for confirmation
+#[derive(Debug, Clone)]
+pub struct PendingSave {
+    pub path: PathBuf,
+    pub content: String,
+    /// Unified diff against the current file, `None` for a new file
+    pub diff: Option<String>,
+}
+
+/// Destination proposed for block `number`, from its file name hint or language.
+/// Hints pointing outside the current directory are not proposed.
+pub fn suggest_path(block: &CodeBlock, number: usize) -> String {
+    block
+        .filename_hint()
+        .filter(|hint| checked_path(hint).is_ok())
+        .unwrap_or_else(|| format!("snippet-{}.{}", number, block.extension()))
+}
+
+/// Reads the current file, if any, to diff it against `content`
+pub fn prepare(path: &str, content: &str) -> anyhow::Result<PendingSave> {
+    let path = expand_home(path.trim());
+
+    if path.is_dir() {
+        anyhow::bail!("{} is a directory", path.display());
+    }
+
+    // files are written with a trailing newline
+    let mut content = content.to_string();
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+
+    let diff = match path.exists() {
+        true => {
+            let current = fs::read_to_string(&path)
+                .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+            let name = path.display().to_string();
+
+            Some(
+                TextDiff::from_lines(&current, &content)
+                    .unified_diff()
+                    .header(&name, &name)
+                    .to_string(),
+            )
+        }
+        false => None,
+    };
+
+    Ok(PendingSave {
+        path,
+        content,
+        diff,
+    })
+}
+
+pub fn write(save: &PendingSave) -> anyhow::Result<()> {
+    if let Some(parent) = save.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(&save.path, &save.content)
+        .map_err(|e| anyhow::anyhow!("{}: {}", save.path.display(), e))
+}
@@ -0,0 +1,102 @@
+use regex::Regex;
+
+use crate::models::Message;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Match {
+    pub message_idx: usize,
+}
+
+/// In-chat find state, scoped to the active chat
+#[derive(Debug, Default, Clone)]
+pub struct Search {
+    pub query: String,
+    pub matches: Vec<Match>,
+    pub current: usize,
+}
+
+impl Search {
+    /// Case-insensitive matcher for the current query
+    pub fn regex(&self) -> Option<Regex> {
+        if self.query.is_empty() {
+            return None;
+        }
+
+        Regex::new(&format!("(?i){}", regex::escape(&self.query))).ok()
+    }
+
+    pub fn run(&mut self, query: &str, messages: &[Message]) {
+        self.query = query.to_string();
+        self.matches.clear();
+        self.current = 0;
+
+        let Some(re) = self.regex() else {
+            return;
+        };
+
+        for (message_idx, message) in messages.iter().enumerate() {
+            // only what the conversation shows, n/N must land on visible matches
+            let count: usize = message
+                .shown_lines()
+                .map(|line| re.find_iter(line).count())
+                .sum();
+            self.matches
+                .extend(std::iter::repeat_n(Match { message_idx }, count));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.matches.clear();
+        self.current = 0;
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.query.is_empty()
+    }
+
+    pub fn current_match(&self) -> Option<&Match> {
+        self.matches.get(self.current)
+    }
+
+    /// Message of the current match, and which of its matches it is
+    pub fn current_position(&self) -> Option<(usize, usize)> {
+        let message_idx = self.current_match()?.message_idx;
+
+        let first = self
+            .matches
+            .iter()
+            .position(|m| m.message_idx == message_idx)?;
+
+        Some((message_idx, self.current - first))
+    }
+
+    pub fn next(&mut self) -> Option<&Match> {
+        if self.matches.is_empty() {
+            return None;
+        }
+
+        self.current = (self.current + 1) % self.matches.len();
+        self.current_match()
+    }
+
+    pub fn prev(&mut self) -> Option<&Match> {
+        if self.matches.is_empty() {
+            return None;
+        }
+
+        self.current = match self.current {
+            0 => self.matches.len() - 1,
+            i => i - 1,
+        };
+        self.current_match()
+    }
+
+    /// e.g. `match 3/12`
+    pub fn status(&self) -> String {
+        match self.matches.len() {
+            0 => format!("no matches for \"{}\"", self.query),
+            total => format!("match {}/{}", self.current + 1, total),
+        }
+    }
+}
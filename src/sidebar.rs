@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::Chat;
+
+/// A row of the chats tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SidebarItem {
+    /// Non selectable group label, e.g. "Pinned" or "Today"
+    Header(String),
+    Folder {
+        name: String,
+        collapsed: bool,
+        count: usize,
+    },
+    /// Index into `App::chats`
+    Chat(usize),
+}
+
+impl SidebarItem {
+    pub fn is_selectable(&self) -> bool {
+        !matches!(self, SidebarItem::Header(_))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Activity,
+    Title,
+    Created,
+}
+
+impl SortBy {
+    pub fn next(self) -> Self {
+        match self {
+            SortBy::Activity => SortBy::Title,
+            SortBy::Title => SortBy::Created,
+            SortBy::Created => SortBy::Activity,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortBy::Activity => "recent",
+            SortBy::Title => "title",
+            SortBy::Created => "created",
+        }
+    }
+
+    fn date(self, chat: &Chat) -> Option<DateTime<Utc>> {
+        match self {
+            SortBy::Activity => Some(chat.updated_at),
+            SortBy::Created => Some(chat.created_at),
+            SortBy::Title => None,
+        }
+    }
+
+    fn sort(self, chats: &[Chat], indexes: &mut [usize]) {
+        match self {
+            SortBy::Title => indexes.sort_by_key(|i| chats[*i].title.to_lowercase()),
+            _ => indexes.sort_by_key(|i| std::cmp::Reverse(self.date(&chats[*i]))),
+        }
+    }
+}
+
+/// Relative day bucket used to group the chats list
+pub fn date_group(date: DateTime<Utc>, now: DateTime<Local>) -> &'static str {
+    let days = now
+        .date_naive()
+        .signed_duration_since(date.with_timezone(&Local).date_naive())
+        .num_days();
+
+    match days {
+        i64::MIN..=0 => "Today",
+        1 => "Yesterday",
+        2..=7 => "Last week",
+        8..=30 => "Last month",
+        _ => "Older",
+    }
+}
+
+/// Pinned chats come first, then chats without a folder (grouped by date unless sorted
+/// by title), then each folder (alphabetically) with its chats.
+/// When `tag_filter` is set only the chats carrying that tag are listed.
+pub fn build(
+    chats: &[Chat],
+    collapsed: &[String],
+    tag_filter: Option<&str>,
+    sort_by: SortBy,
+) -> Vec<SidebarItem> {
+    let mut visible: Vec<usize> = (0..chats.len())
+        .filter(|i| tag_filter.is_none_or(|tag| chats[*i].has_tag(tag)))
+        .collect();
+
+    sort_by.sort(chats, &mut visible);
+
+    let mut rows: Vec<SidebarItem> = Vec::new();
+
+    let pinned: Vec<usize> = visible
+        .iter()
+        .copied()
+        .filter(|i| chats[*i].pinned)
+        .collect();
+
+    if !pinned.is_empty() {
+        rows.push(SidebarItem::Header("Pinned".to_string()));
+        rows.extend(pinned.into_iter().map(SidebarItem::Chat));
+    }
+
+    let mut folders: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    let mut current_group: Option<&str> = None;
+    let now = Local::now();
+
+    for idx in visible.into_iter().filter(|i| !chats[*i].pinned) {
+        let chat = &chats[idx];
+
+        if let Some(folder) = &chat.folder {
+            folders.entry(folder.as_str()).or_default().push(idx);
+            continue;
+        }
+
+        if let Some(date) = sort_by.date(chat) {
+            let group = date_group(date, now);
+
+            if current_group != Some(group) {
+                rows.push(SidebarItem::Header(group.to_string()));
+                current_group = Some(group);
+            }
+        }
+
+        rows.push(SidebarItem::Chat(idx));
+    }
+
+    for (name, chat_indexes) in folders {
+        let is_collapsed = collapsed.iter().any(|c| c == name);
+
+        rows.push(SidebarItem::Folder {
+            name: name.to_string(),
+            collapsed: is_collapsed,
+            count: chat_indexes.len(),
+        });
+
+        if !is_collapsed {
+            rows.extend(chat_indexes.into_iter().map(SidebarItem::Chat));
+        }
+    }
+
+    rows
+}
@@ -0,0 +1,112 @@
+use crate::export::ExportFormat;
+
+/// Commands typed in the input, e.g. `/model gpt-4o`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlashCommand {
+    Model(String),
+    System(String),
+    Clear,
+    Title(String),
+    Retry,
+    Export(ExportFormat),
+    /// Audio file whose transcript replaces the input
+    Transcribe(String),
+}
+
+/// Name, argument hint and known argument values
+const COMMANDS: [(&str, &str, &[&str]); 7] = [
+    (
+        "model",
+        "<name>",
+        &[
+            "gpt-3.5-turbo",
+            "gpt-4",
+            "gpt-4-turbo",
+            "gpt-4o",
+            "gpt-4o-mini",
+        ],
+    ),
+    ("system", "<prompt>", &[]),
+    ("clear", "", &[]),
+    ("title", "<title>", &[]),
+    ("retry", "", &[]),
+    ("export", "md|json|html", &["md", "json", "html"]),
+    ("transcribe", "@<audio file>", &[]),
+];
+
+/// `None` when the input is a regular message (`//` escapes a leading slash)
+pub fn parse(input: &str) -> Option<Result<SlashCommand, String>> {
+    let rest = input.strip_prefix('/')?;
+
+    if rest.starts_with('/') {
+        return None;
+    }
+
+    let (name, arg) = match rest.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (rest, ""),
+    };
+
+    let required = |arg: &str| match arg.is_empty() {
+        true => Err(format!("/{} needs an argument", name)),
+        false => Ok(arg.to_string()),
+    };
+
+    let command = match name {
+        "model" => required(arg).map(SlashCommand::Model),
+        "system" => required(arg).map(SlashCommand::System),
+        "title" => required(arg).map(SlashCommand::Title),
+        // `@` so that the path completes like attachments
+        "transcribe" => required(arg.trim_start_matches('@')).map(SlashCommand::Transcribe),
+        "clear" => Ok(SlashCommand::Clear),
+        "retry" => Ok(SlashCommand::Retry),
+        "export" => match ExportFormat::from_extension(if arg.is_empty() { "md" } else { arg }) {
+            Some(format) => Ok(SlashCommand::Export(format)),
+            None => Err(format!(
+                "Unknown export format `{}`, use md, json or html",
+                arg
+            )),
+        },
+        _ => Err(format!("Unknown command /{}", name)),
+    };
+
+    Some(command)
+}
+
+/// Text to append to `input` to complete the command name or argument
+pub fn completion(input: &str) -> Option<String> {
+    let rest = input.strip_prefix('/')?;
+
+    match rest.split_once(' ') {
+        None => COMMANDS
+            .iter()
+            .find(|(name, _, _)| name.starts_with(rest) && *name != rest)
+            .map(|(name, _, _)| format!("{} ", &name[rest.len()..])),
+        Some((name, arg)) => {
+            COMMANDS
+                .iter()
+                .find(|(n, _, _)| *n == name)
+                .and_then(|(_, _, values)| {
+                    values
+                        .iter()
+                        .find(|v| v.starts_with(arg) && **v != arg)
+                        .map(|v| v[arg.len()..].to_string())
+                })
+        }
+    }
+}
+
+/// Dimmed hint shown after the cursor: the completion, or the argument format
+pub fn hint(input: &str) -> Option<String> {
+    if let Some(completion) = completion(input) {
+        return Some(completion);
+    }
+
+    let rest = input.strip_prefix('/')?;
+    let name = rest.strip_suffix(' ')?;
+
+    COMMANDS
+        .iter()
+        .find(|(n, hint, _)| *n == name && !hint.is_empty())
+        .map(|(_, hint, _)| hint.to_string())
+}
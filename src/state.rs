@@ -1,13 +1,86 @@
-use crate::{openai::send_message, utils::trim_spaces};
-use crossterm::event::KeyCode;
+use crate::{
+    attach, clipboard,
+    commands::Command,
+    config::Config,
+    export, import,
+    keymap::Keymap,
+    layout::PaneLayout,
+    markdown::{self, CodeBlock},
+    mouse::Areas,
+    openai::{generate_title, send_message, transcribe},
+    patch::{self, PatchReview},
+    run::{self, PendingRun},
+    save::{self, PendingSave},
+    search::Search,
+    sidebar::{self, SidebarItem, SortBy},
+    slash::{self, SlashCommand},
+    storage::{self, expand_home, SavedState},
+    theme::{self, Theme},
+    tools::{self, PendingCalls},
+    utils::{fuzzy_score, trim_spaces},
+    viewer::Viewer,
+};
+use crossterm::event::{KeyCode, MouseButton, MouseEvent, MouseEventKind};
+use std::collections::HashSet;
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::components::*;
 use crate::models::*;
 
+/// Lines scrolled per mouse wheel step
+const WHEEL_LINES: usize = 3;
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Modal {
     NewChat,
     RenameChat,
+    Search,
+    TagChat,
+    MoveChat,
+    FilterByTag,
+    Export(ExportScope),
+    Import,
+    Palette,
+    SwitchModel,
+    /// Asks which of the selected message's code blocks to copy
+    YankCodeBlock(usize),
+    /// Lists the selected message's code blocks
+    PickCodeBlock(BlockAction),
+    SaveCodeBlock,
+    /// Shows the diff of `App::pending_save`
+    ConfirmSave,
+    /// Hunk by hunk review of `App::patch_review`
+    ApplyPatch,
+    /// Shows `App::pending_run`, the user has to type `run` or `send`
+    ConfirmRun,
+    /// Shows the `!command` of `App::pending_message` before running it, then
+    /// its output before sending it
+    ConfirmCommand,
+    /// Asks before running the first of `App::tool_calls`
+    ApproveTool,
+}
+
+impl Modal {
+    /// Max input length, paths need more room than titles
+    pub fn input_limit(&self) -> usize {
+        match self {
+            Modal::Export(_) | Modal::Import | Modal::SaveCodeBlock => 250,
+            _ => 25,
+        }
+    }
+}
+
+/// What to do with the code block picked in `Modal::PickCodeBlock`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockAction {
+    Save,
+    Run,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExportScope {
+    SelectedChat,
+    AllChats,
 }
 
 #[derive(Clone, Eq, PartialEq, Copy)]
@@ -15,25 +88,106 @@ pub enum Section {
     Chats,
     Messages,
     Input,
+    Viewer,
     Modal,
 }
 
+/// Results of background work, delivered to `App::handle_event` by the main loop
+#[derive(Debug)]
+pub enum AppEvent {
+    /// Sent when a title request ends, `None` when it failed
+    ChatTitled { chat_id: ID, title: Option<String> },
+    /// Output of a code block, appended to the message `message_id`
+    RunOutput {
+        chat_id: ID,
+        message_id: ID,
+        text: String,
+    },
+    /// Output of the `!command` of `App::pending_message`
+    CommandOutput {
+        message_id: ID,
+        output: Result<Attachment, String>,
+    },
+    /// Result of the first of `App::tool_calls`
+    ToolResult {
+        chat_id: ID,
+        call_id: String,
+        result: String,
+    },
+    RunFinished {
+        chat_id: ID,
+        message_id: ID,
+        status: String,
+        /// Send the output to the model once done
+        send: bool,
+    },
+}
+
 #[derive(Clone)]
 pub struct App {
+    pub config: Config,
+    pub keymap: Keymap,
+    pub themes: Vec<Theme>,
+    pub theme: Theme,
+    /// Theme picked at runtime, saved for the next start
+    pub picked_theme: Option<String>,
+    pub events: Option<UnboundedSender<AppEvent>>,
     pub loading: bool,
     pub active_chat_idx: Option<usize>,
     pub chats: StatefulList<Chat>,
+    pub sidebar: StatefulList<SidebarItem>,
+    pub collapsed_folders: Vec<String>,
+    pub tag_filter: Option<String>,
+    pub sort_by: SortBy,
+    pub layout: PaneLayout,
+    pub dirty: bool,
+    /// Chats with a title request in flight
+    pub titling: HashSet<ID>,
     pub input: Input,
     pub modal_input: Input,
     pub section: Section,
     pub focus: Option<Section>,
     pub modal: Option<Modal>,
+    pub palette: StatefulList<Command>,
+    /// Focus to go back to when the palette closes
+    pub palette_return: (Section, Option<Section>),
+    pub should_quit: bool,
+    /// One-off feedback shown in the help bar until the next key press
+    pub notice: Option<String>,
+    pub search: Search,
+    pub areas: Areas,
+    /// Full screen view of a message, shown while focused
+    pub viewer: Option<Viewer>,
+    /// Text for the main loop to show in `$PAGER`, it needs the terminal
+    pub pager: Option<String>,
+    /// Code blocks of the selected message, for `Modal::PickCodeBlock`
+    pub code_blocks: StatefulList<CodeBlock>,
+    pub pending_save: Option<PendingSave>,
+    pub patch_review: Option<PatchReview>,
+    pub pending_run: Option<PendingRun>,
+    /// Message waiting for its `!command` to be confirmed, then its output checked
+    pub pending_message: Option<Message>,
+    /// The `!command` of `App::pending_message` is running
+    pub command_running: bool,
+    pub tool_calls: Option<PendingCalls>,
+    /// Scroll of previews shown in modals
+    pub preview_scroll: Scroll,
 }
 
 impl Default for App {
     fn default() -> Self {
         Self {
+            config: Config::default(),
+            keymap: Keymap::default(),
+            themes: vec![Theme::dark(), Theme::light()],
+            theme: Theme::dark(),
+            picked_theme: None,
+            events: None,
             modal: None,
+            notice: None,
+            palette: StatefulList::with_items(Command::ALL.to_vec()),
+            palette_return: (Section::Chats, Some(Section::Chats)),
+            should_quit: false,
             loading: false,
             section: Section::Chats,
             focus: Some(Section::Chats),
@@ -41,10 +195,30 @@ impl Default for App {
             modal_input: Input::new(25),
             active_chat_idx: None,
             chats: StatefulList::default(),
+            sidebar: StatefulList::default(),
+            collapsed_folders: vec![],
+            tag_filter: None,
+            sort_by: SortBy::default(),
+            layout: PaneLayout::default(),
+            dirty: false,
+            titling: HashSet::new(),
+            search: Search::default(),
+            areas: Areas::default(),
+            viewer: None,
+            pager: None,
+            code_blocks: StatefulList::default(),
+            pending_save: None,
+            patch_review: None,
+            pending_run: None,
+            pending_message: None,
+            command_running: false,
+            tool_calls: None,
+            preview_scroll: Scroll::default(),
         }
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Action {
     Up,
     Down,
@@ -55,24 +229,21 @@ pub enum Action {
     Char(char),
     Key(KeyCode),
     Backspace,
+    Command(Command),
 }
 
 impl App {
-    #[allow(dead_code)]
-    pub fn is_focused(&self, s: Section) -> bool {
-        if let Some(focus) = self.focus {
-            return focus == s;
+    pub fn focus(&mut self, section: Section) {
+        if section != Section::Viewer {
+            self.viewer = None;
         }
 
-        false
-    }
-
-    pub fn focus(&mut self, section: Section) {
         self.focus = Some(section);
         self.section = section;
     }
 
     pub fn open_modal(&mut self, modal: Modal, input_value: Option<String>) {
+        self.modal_input.max_length = modal.input_limit();
         self.modal = Some(modal);
 
         self.focus = Some(Section::Modal);
@@ -88,209 +259,1812 @@ impl App {
         self.modal_input.clear();
     }
 
-    pub fn select_current_chat(&mut self) {
-        if let Some(i) = self.active_chat_idx {
-            self.chats.select(i);
-        } else {
-            self.chats.select_first();
+    pub fn restore(&mut self, state: SavedState) {
+        self.chats.items = state.chats;
+        self.collapsed_folders = state.collapsed_folders;
+        self.sort_by = state.sort_by;
+        self.layout = state.layout;
+
+        // a theme set in the config wins over the one picked last time
+        if let Some(name) = state
+            .theme
+            .as_deref()
+            .filter(|_| self.config.theme.is_none())
+        {
+            self.set_theme(name);
         }
+        self.picked_theme = state.theme;
+
+        self.refresh_sidebar();
     }
 
-    pub async fn dispatch(&mut self, action: Action) -> anyhow::Result<()> {
-        match &self.focus {
-            None => match self.section {
-                Section::Chats if matches!(action, Action::Enter) => {
-                    self.focus(Section::Chats);
-                    self.select_current_chat()
-                }
-                Section::Messages | Section::Input
-                    if matches!(
-                        action,
-                        Action::Esc | Action::Left | Action::Right | Action::Char('c')
-                    ) =>
-                {
-                    self.focus(Section::Chats);
-                    self.input.clear();
-                }
-                Section::Messages => match action {
-                    Action::Enter => self.focus(Section::Messages),
-                    Action::Up | Action::Down => self.section = Section::Input,
-                    Action::Char('i') => self.focus(Section::Input),
-                    _ => {}
-                },
-                Section::Input => match action {
-                    Action::Char('m') => self.focus(Section::Messages),
-                    Action::Enter => self.focus(Section::Input),
-                    Action::Up | Action::Down => self.section = Section::Messages,
-                    _ => {}
-                },
-                _ => {}
-            },
-            Some(section) => match section {
-                Section::Modal => match action {
-                    Action::Esc => {
-                        match self.modal {
-                            Some(Modal::RenameChat) | Some(Modal::NewChat) => {
-                                self.section = Section::Chats;
-                                self.focus = Some(Section::Chats);
+    pub fn saved_state(&self) -> SavedState {
+        let mut chats = self.chats.items.clone();
+        chats.iter_mut().for_each(Chat::unload_attachments);
 
-                                self.select_current_chat();
-                            }
-                            _ => {}
-                        };
+        SavedState {
+            chats,
+            collapsed_folders: self.collapsed_folders.clone(),
+            sort_by: self.sort_by,
+            layout: self.layout,
+            theme: self.picked_theme.clone(),
+        }
+    }
 
-                        self.close_modal();
-                    }
-                    Action::Char(to_enter) => self.modal_input.insert(to_enter),
-                    Action::Backspace => self.modal_input.delete(),
-                    Action::Left => self.modal_input.left(),
-                    Action::Right => self.modal_input.right(),
-                    Action::Enter => match self.modal {
-                        Some(Modal::NewChat) => {
-                            let title = &self.modal_input.text.clone();
-                            self.new_chat(title);
-                            self.close_modal();
-                            self.focus(Section::Chats);
-                        }
-                        Some(Modal::RenameChat) => {
-                            let title = &self.modal_input.text.clone();
-                            self.rename_current_chat(title);
-                            self.close_modal();
-                            self.focus(Section::Chats);
-                        }
-                        None => {}
-                    },
-                    _ => {}
-                },
-                Section::Chats => match action {
-                    Action::Up => self.chats.prev(),
-                    Action::Down => self.chats.next(),
-                    Action::Enter => {
-                        if let Some(chat_idx) = self.chats.state.selected() {
-                            self.active_chat_idx = Some(chat_idx);
-                            self.focus(Section::Input);
-                        }
-                    }
-                    Action::Backspace => self.delete_current_chat(),
-                    Action::Char('N') => self.append_new_chat(),
-                    Action::Char('n') => self.open_modal(Modal::NewChat, None),
-                    Action::Char('r') => {
-                        if let Some(chat_idx) = self.chats.state.selected() {
-                            if let Some(chat) = self.chats.items.get(chat_idx) {
-                                self.open_modal(Modal::RenameChat, Some(chat.title.clone()));
-                            }
-                        }
-                    }
-                    _ => {}
-                },
-                Section::Messages => {
-                    if let Some(chat) = self.get_active_chat_mut() {
-                        match action {
-                            Action::Backspace => self.delete_message(),
-                            Action::Up => chat.messages.prev(),
-                            Action::Down => chat.messages.next(),
-                            Action::Esc => self.blur(),
-                            _ => {}
-                        }
-                    } else {
-                        match action {
-                            Action::Backspace => self.delete_message(),
-                            Action::Esc => self.blur(),
-                            _ => {}
-                        }
-                    }
-                }
-                Section::Input => match action {
-                    Action::Char(to_enter) => self.input.insert(to_enter),
-                    Action::Enter => self.submit_message().await?,
-                    Action::Backspace => self.input.delete(),
-                    Action::Left => self.input.left(),
-                    Action::Right => self.input.right(),
-                    Action::Esc => self.blur(),
-                    _ => {}
-                },
-            },
+    /// Writes the chats to disk if anything changed since the last save. On
+    /// failure the chats stay dirty, so the next call tries again.
+    pub fn persist(&mut self) {
+        if !self.dirty {
+            return;
+        }
+
+        match storage::save(&self.saved_state()) {
+            Ok(()) => self.dirty = false,
+            Err(e) => self.notice = Some(format!("Saving chats failed: {}", e)),
+        }
+    }
+
+    /// Rebuilds the chats tree, keeping the selected chat or folder selected
+    /// wherever it moved, or else the selected row position
+    pub fn refresh_sidebar(&mut self) {
+        // chats are followed by ID, indices shift when one is deleted
+        enum Selected {
+            Chat(ID),
+            Folder(String),
+        }
+
+        let selected = self.sidebar.state.selected().unwrap_or(0);
+        let selected_item = match self.sidebar.items.get(selected) {
+            Some(SidebarItem::Chat(idx)) => self
+                .chats
+                .items
+                .get(*idx)
+                .map(|c| Selected::Chat(c.id.clone())),
+            Some(SidebarItem::Folder { name, .. }) => Some(Selected::Folder(name.clone())),
+            _ => None,
         };
 
-        Ok(())
+        self.sidebar.items = sidebar::build(
+            &self.chats.items,
+            &self.collapsed_folders,
+            self.tag_filter.as_deref(),
+            self.sort_by,
+        );
+
+        if self.sidebar.items.is_empty() {
+            self.sidebar.unselect();
+            return;
+        }
+
+        let same = selected_item.and_then(|selected_item| {
+            self.sidebar
+                .items
+                .iter()
+                .position(|item| match (item, &selected_item) {
+                    (SidebarItem::Chat(idx), Selected::Chat(id)) => {
+                        self.chats.items.get(*idx).is_some_and(|c| &c.id == id)
+                    }
+                    (SidebarItem::Folder { name, .. }, Selected::Folder(folder)) => name == folder,
+                    _ => false,
+                })
+        });
+
+        match same {
+            Some(row) => self.sidebar.select(row),
+            None => {
+                self.sidebar
+                    .select(selected.min(self.sidebar.items.len() - 1));
+                self.skip_sidebar_headers(true);
+            }
+        }
     }
 
-    pub fn get_active_chat_mut(&mut self) -> Option<&mut Chat> {
-        match self.active_chat_idx {
-            Some(index) => self.chats.items.get_mut(index),
-            None => None,
+    pub fn sidebar_next(&mut self) {
+        self.sidebar.next();
+        self.skip_sidebar_headers(true);
+    }
+
+    pub fn sidebar_prev(&mut self) {
+        self.sidebar.prev();
+        self.skip_sidebar_headers(false);
+    }
+
+    pub fn select_first_sidebar_row(&mut self) {
+        self.sidebar.select_first();
+        self.skip_sidebar_headers(true);
+    }
+
+    /// Moves the selection off group headers, which can't be selected
+    fn skip_sidebar_headers(&mut self, forward: bool) {
+        for _ in 0..self.sidebar.items.len() {
+            let on_header = self
+                .sidebar
+                .state
+                .selected()
+                .and_then(|i| self.sidebar.items.get(i))
+                .is_some_and(|item| !item.is_selectable());
+
+            if !on_header {
+                return;
+            }
+
+            match forward {
+                true => self.sidebar.next(),
+                false => self.sidebar.prev(),
+            }
         }
     }
 
-    pub fn rename_current_chat(&mut self, title: &str) {
-        if let Some(i) = self.chats.state.selected() {
+    pub fn cycle_sort(&mut self) {
+        self.sort_by = self.sort_by.next();
+        self.dirty = true;
+        self.refresh_sidebar();
+        self.select_first_sidebar_row();
+    }
+
+    /// Hiding the sidebar moves away from it, it would stay visible otherwise
+    pub fn toggle_sidebar(&mut self) {
+        self.layout.sidebar = !self.layout.sidebar;
+        self.dirty = true;
+
+        if !self.layout.sidebar && self.section == Section::Chats {
+            self.focus(Section::Messages);
+        }
+    }
+
+    fn resize_layout(&mut self, resize: impl FnOnce(&mut PaneLayout)) {
+        resize(&mut self.layout);
+        self.layout.zoom = false;
+        self.dirty = true;
+    }
+
+    pub fn toggle_pin_current_chat(&mut self) {
+        if let Some(i) = self.selected_chat_idx() {
             if let Some(chat) = self.chats.items.get_mut(i) {
-                chat.title = title.to_string();
+                chat.pinned = !chat.pinned;
             }
+
+            self.dirty = true;
+            self.refresh_sidebar();
+            self.select_chat_row(i);
         }
     }
 
-    pub fn blur(&mut self) {
-        self.focus = None;
+    pub fn selected_chat_idx(&self) -> Option<usize> {
+        match self
+            .sidebar
+            .state
+            .selected()
+            .and_then(|i| self.sidebar.items.get(i))
+        {
+            Some(SidebarItem::Chat(idx)) => Some(*idx),
+            _ => None,
+        }
     }
 
-    pub fn delete_message(&mut self) {
-        if let Some(chat) = self.get_active_chat_mut() {
-            if let Some(index) = chat.messages.state.selected() {
-                chat.messages.prev();
-                chat.messages.items.remove(index);
-            }
+    /// Selects the sidebar row of the given chat, returns false if it is hidden
+    pub fn select_chat_row(&mut self, chat_idx: usize) -> bool {
+        let row = self
+            .sidebar
+            .items
+            .iter()
+            .position(|item| item == &SidebarItem::Chat(chat_idx));
+
+        if let Some(row) = row {
+            self.sidebar.select(row);
         }
+
+        row.is_some()
     }
 
-    pub async fn submit_message(&mut self) -> anyhow::Result<()> {
-        if self.input.is_empty() {
-            self.input.clear();
-            return Ok(());
+    pub fn select_current_chat(&mut self) {
+        let selected = match self.active_chat_idx {
+            Some(i) => self.select_chat_row(i),
+            None => false,
+        };
+
+        if !selected {
+            self.select_first_sidebar_row();
         }
+    }
 
-        self.loading = true;
+    pub fn toggle_folder(&mut self, name: &str) {
+        match self.collapsed_folders.iter().position(|f| f == name) {
+            Some(i) => {
+                self.collapsed_folders.remove(i);
+            }
+            None => self.collapsed_folders.push(name.to_string()),
+        }
 
-        let message = Message::new(Role::User, trim_spaces(&self.input.text.clone()).as_str());
+        self.dirty = true;
+        self.refresh_sidebar();
+    }
 
-        self.input.clear();
+    pub fn set_tag_filter(&mut self, tag: &str) {
+        let tag = tag.trim().trim_start_matches('#').to_lowercase();
 
-        if let Some(active_chat_index) = self.active_chat_idx {
-            if let Some(chat) = self.chats.items.get_mut(active_chat_index) {
-                chat.append_message(message);
+        self.tag_filter = match tag.is_empty() {
+            true => None,
+            false => Some(tag),
+        };
+
+        self.refresh_sidebar();
+        self.select_first_sidebar_row();
+    }
 
-                if let Some(m) = send_message(chat.clone()).await? {
-                    chat.append_message(m);
+    pub fn tag_current_chat(&mut self, tags: &str) {
+        if let Some(i) = self.selected_chat_idx() {
+            if let Some(chat) = self.chats.items.get_mut(i) {
+                chat.set_tags(tags);
+            }
+
+            self.dirty = true;
+            self.refresh_sidebar();
+            self.select_chat_row(i);
+        }
+    }
+
+    pub fn move_current_chat(&mut self, folder: &str) {
+        if let Some(i) = self.selected_chat_idx() {
+            if let Some(chat) = self.chats.items.get_mut(i) {
+                chat.move_to_folder(folder);
+
+                // make sure the chat stays visible in its new place
+                if let Some(folder) = &chat.folder {
+                    self.collapsed_folders.retain(|f| f != folder);
                 }
             }
+
+            self.dirty = true;
+            self.refresh_sidebar();
+            self.select_chat_row(i);
         }
+    }
 
-        self.input.clear();
+    /// Returns false when there is no theme with that name
+    pub fn set_theme(&mut self, name: &str) -> bool {
+        let Some(theme) = self.themes.iter().find(|t| t.name == name) else {
+            return false;
+        };
 
-        self.loading = false;
+        self.theme = match theme::no_color() {
+            true => theme.without_colors(),
+            false => theme.clone(),
+        };
 
-        Ok(())
+        true
     }
 
-    pub fn append_new_chat(&mut self) {
-        self.new_chat(&format!("chat #{}", self.chats.items.len()));
+    pub fn cycle_theme(&mut self) {
+        let current = self
+            .themes
+            .iter()
+            .position(|t| t.name == self.theme.name)
+            .unwrap_or(0);
+
+        let next = self.themes[(current + 1) % self.themes.len()].name.clone();
+
+        self.set_theme(&next);
+        self.picked_theme = Some(next.clone());
+        self.dirty = true;
+        self.notice = Some(format!("Theme: {}", next));
+    }
+
+    pub fn open_palette(&mut self) {
+        if self.modal == Some(Modal::Palette) {
+            return;
+        }
+
+        self.palette_return = (self.section, self.focus);
+        self.open_modal(Modal::Palette, None);
+        self.filter_palette();
     }
 
-    pub fn new_chat(&mut self, title: &str) {
-        self.chats.items.push(Chat::new(title));
-        self.chats.select_last();
+    fn close_palette(&mut self) {
+        self.close_modal();
+        (self.section, self.focus) = self.palette_return;
     }
 
-    pub fn delete_current_chat(&mut self) {
-        if let Some(i) = self.chats.state.selected() {
-            self.chats.items.remove(i);
+    /// Fuzzy filters the palette commands on the modal input
+    pub fn filter_palette(&mut self) {
+        let query = self.modal_input.text.clone();
+
+        let mut scored: Vec<(i64, Command)> = Command::ALL
+            .iter()
+            .filter_map(|c| fuzzy_score(&query, c.title()).map(|score| (score, *c)))
+            .collect();
+
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        self.palette.items = scored.into_iter().map(|(_, c)| c).collect();
+        self.palette.unselect();
+        self.palette.select_first();
+    }
+
+    fn run_palette_selection(&mut self) {
+        let command = self
+            .palette
+            .state
+            .selected()
+            .and_then(|i| self.palette.items.get(i).copied());
+
+        self.close_palette();
+
+        if let Some(command) = command {
+            // chat commands act on the open chat when launched outside the sidebar
+            if command.is_chat_scoped() && self.focus != Some(Section::Chats) {
+                self.select_current_chat();
+            }
+
+            self.run_command(command);
+        }
+    }
+
+    /// Scrolls the viewer when it is open, the messages otherwise
+    fn scroll_messages(&mut self, command: Command) {
+        let scroll = match (&mut self.viewer, self.active_chat_idx) {
+            (Some(viewer), _) => &mut viewer.scroll,
+            (None, Some(i)) => match self.chats.items.get_mut(i) {
+                Some(chat) => &mut chat.scroll,
+                None => return,
+            },
+            (None, None) => return,
+        };
+
+        match command {
+            Command::ScrollUp => scroll.up(1),
+            Command::ScrollDown => scroll.down(1),
+            Command::ScrollPageUp => scroll.page_up(),
+            Command::ScrollPageDown => scroll.page_down(),
+            Command::ScrollHalfPageUp => scroll.half_page_up(),
+            Command::ScrollHalfPageDown => scroll.half_page_down(),
+            Command::ScrollTop => scroll.top(),
+            Command::ScrollBottom => scroll.bottom(),
+            _ => {}
+        }
+    }
+
+    fn selected_message(&mut self) -> Option<(usize, usize, Message)> {
+        let chat = self.get_active_chat_mut()?;
+        let idx = chat.messages.state.selected()?;
+        let message = chat.messages.items.get(idx)?.clone();
+
+        Some((idx, chat.messages.items.len(), message))
+    }
+
+    pub fn view_message(&mut self) {
+        let Some((idx, count, message)) = self.selected_message() else {
+            self.notice = Some("Select a message first".to_string());
+            return;
+        };
+
+        let title = format!("{} message {}/{}", message.role, idx + 1, count);
+        self.viewer = Some(Viewer::new(title, message.content));
+        self.focus(Section::Viewer);
+    }
 
-            if self.chats.items.is_empty() {
-                self.chats.unselect();
+    pub fn close_viewer(&mut self) {
+        self.focus(Section::Messages);
+    }
+
+    /// Back from a modal opened on the selected message
+    fn focus_message_view(&mut self) {
+        match self.viewer {
+            Some(_) => self.focus(Section::Viewer),
+            None => self.focus(Section::Messages),
+        }
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str, what: &str) {
+        self.notice = Some(match clipboard::copy(text, &self.config.clipboard) {
+            Ok(method) => format!(
+                "Copied {} ({} lines) via {}",
+                what,
+                text.lines().count(),
+                method
+            ),
+            Err(e) => format!("Copy failed: {}", e),
+        });
+    }
+
+    pub fn yank_message(&mut self, as_quote: bool) {
+        let Some((_, _, message)) = self.selected_message() else {
+            self.notice = Some("Select a message first".to_string());
+            return;
+        };
+
+        match as_quote {
+            true => self.copy_to_clipboard(&markdown::quote(&message.content), "quote"),
+            false => self.copy_to_clipboard(&message.content, "message"),
+        }
+    }
+
+    /// Copies code block `number` (1-based), asking for it when there are several
+    pub fn yank_code_block(&mut self, number: Option<usize>) {
+        let Some((_, _, message)) = self.selected_message() else {
+            self.notice = Some("Select a message first".to_string());
+            return;
+        };
+
+        let blocks = markdown::code_blocks(&message.content);
+
+        let number = match (number, blocks.len()) {
+            (_, 0) => {
+                self.notice = Some("No code blocks in this message".to_string());
+                return;
+            }
+            (Some(number), _) => number,
+            (None, 1) => 1,
+            (None, count) => {
+                self.open_modal(Modal::YankCodeBlock(count), Some("1".to_string()));
                 return;
             }
+        };
+
+        match number.checked_sub(1).and_then(|i| blocks.get(i)) {
+            Some(block) => {
+                let what = format!("code block {}/{}", number, blocks.len());
+                self.copy_to_clipboard(&block.code, &what);
+            }
+            None => self.notice = Some(format!("No code block {}", number)),
+        }
+    }
+
+    /// Lists the selected message's code blocks, skipping the list when there is one
+    pub fn pick_code_block(&mut self, block_action: BlockAction) {
+        let Some((_, _, message)) = self.selected_message() else {
+            self.notice = Some("Select a message first".to_string());
+            return;
+        };
 
-            self.chats.prev();
+        self.code_blocks = StatefulList::with_items(markdown::code_blocks(&message.content));
+        self.code_blocks.select_first();
+
+        match self.code_blocks.items.len() {
+            0 => self.notice = Some("No code blocks in this message".to_string()),
+            1 => self.run_block_action(block_action),
+            _ => self.open_modal(Modal::PickCodeBlock(block_action), None),
+        }
+    }
+
+    fn run_block_action(&mut self, block_action: BlockAction) {
+        let Some(idx) = self.code_blocks.state.selected() else {
+            return;
+        };
+
+        match block_action {
+            BlockAction::Save => {
+                let path = save::suggest_path(&self.code_blocks.items[idx], idx + 1);
+                self.open_modal(Modal::SaveCodeBlock, Some(path));
+            }
+            BlockAction::Run => self.prepare_run(idx),
+        }
+    }
+
+    fn prepare_run(&mut self, idx: usize) {
+        let chat = self.active_chat_idx.and_then(|i| self.chats.items.get(i));
+
+        let Some(chat_id) = chat.map(|c| c.id.clone()) else {
+            return;
+        };
+
+        let result = run::script(&self.code_blocks.items[idx]).and_then(|script| {
+            run::check_allowed(&script, &self.config.run.allowlist)?;
+            Ok(script)
+        });
+
+        match result {
+            Ok(script) => {
+                self.pending_run = Some(PendingRun { chat_id, script });
+                self.open_modal(Modal::ConfirmRun, None);
+            }
+            Err(e) => {
+                self.notice = Some(format!("Not run: {}", e));
+                self.focus_message_view();
+            }
+        }
+    }
+
+    /// Runs `App::pending_run`, its output goes to a new message of its chat
+    fn confirm_run(&mut self, send: bool) {
+        self.close_modal();
+        self.focus_message_view();
+
+        let (Some(pending), Some(events)) = (self.pending_run.take(), self.events.clone()) else {
+            return;
+        };
+
+        let Some(chat) = self
+            .chats
+            .items
+            .iter_mut()
+            .find(|c| c.id == pending.chat_id)
+        else {
+            return;
+        };
+
+        let message = Message::user(&format!(
+            "Output of:\n```sh\n{}\n```\n```\n",
+            pending.script.trim_end()
+        ));
+        let message_id = message.id.clone();
+
+        chat.append_message(message);
+        chat.scroll.follow = true;
+        self.dirty = true;
+        self.notice = Some("Running…".to_string());
+
+        run::spawn(pending, message_id, send, self.config.run.clone(), events);
+    }
+
+    fn prepare_save(&mut self, path: &str) {
+        let block = self
+            .code_blocks
+            .state
+            .selected()
+            .and_then(|i| self.code_blocks.items.get(i));
+
+        let result = match (block, path.trim().is_empty()) {
+            (_, true) => Err(anyhow::anyhow!("no path given")),
+            (None, _) => Err(anyhow::anyhow!("no code block selected")),
+            (Some(block), false) => save::prepare(path, &block.code),
+        };
+
+        match result {
+            Ok(pending) => {
+                self.pending_save = Some(pending);
+                self.preview_scroll = Scroll {
+                    follow: false,
+                    ..Scroll::default()
+                };
+                self.open_modal(Modal::ConfirmSave, None);
+            }
+            Err(e) => {
+                self.notice = Some(format!("Save failed: {}", e));
+                self.focus_message_view();
+            }
+        }
+    }
+
+    fn confirm_save(&mut self) {
+        if let Some(pending) = self.pending_save.take() {
+            self.notice = Some(match save::write(&pending) {
+                Ok(()) => format!(
+                    "Saved {} lines to {}",
+                    pending.content.lines().count(),
+                    pending.path.display()
+                ),
+                Err(e) => format!("Save failed: {}", e),
+            });
+        }
+
+        self.close_modal();
+        self.focus_message_view();
+    }
+
+    /// Reviews the unified diffs of the selected message before applying them
+    pub fn review_patch(&mut self) {
+        let Some((_, _, message)) = self.selected_message() else {
+            self.notice = Some("Select a message first".to_string());
+            return;
+        };
+
+        let files = patch::parse(&message.content);
+
+        if files.is_empty() {
+            self.notice = Some("No diff in this message".to_string());
+            return;
+        }
+
+        self.patch_review = Some(PatchReview::new(files));
+        self.preview_scroll = Scroll {
+            follow: false,
+            ..Scroll::default()
+        };
+        self.open_modal(Modal::ApplyPatch, None);
+    }
+
+    fn apply_patch(&mut self) {
+        if let Some(review) = self.patch_review.take() {
+            self.notice = Some(review.apply().summary());
+        }
+
+        self.close_modal();
+        self.focus_message_view();
+    }
+
+    pub fn open_in_pager(&mut self) {
+        let content = match &self.viewer {
+            Some(viewer) => Some(viewer.content.clone()),
+            None => self.selected_message().map(|(_, _, m)| m.content),
+        };
+
+        match content {
+            Some(content) => self.pager = Some(content),
+            None => self.notice = Some("Select a message first".to_string()),
+        }
+    }
+
+    pub fn run_command(&mut self, command: Command) {
+        let selected_chat = self
+            .selected_chat_idx()
+            .and_then(|i| self.chats.items.get(i));
+
+        match command {
+            Command::NewChat => self.open_modal(Modal::NewChat, None),
+            Command::QuickNewChat => self.append_new_chat(),
+            Command::RenameChat => {
+                if let Some(chat) = selected_chat {
+                    self.open_modal(Modal::RenameChat, Some(chat.title.clone()));
+                }
+            }
+            Command::DeleteChat => self.delete_current_chat(),
+            Command::TagChat => {
+                if let Some(chat) = selected_chat {
+                    self.open_modal(Modal::TagChat, Some(chat.tags.join(", ")));
+                }
+            }
+            Command::MoveChat => {
+                if let Some(chat) = selected_chat {
+                    self.open_modal(Modal::MoveChat, chat.folder.clone());
+                }
+            }
+            Command::FilterByTag => self.open_modal(Modal::FilterByTag, self.tag_filter.clone()),
+            Command::TogglePin => self.toggle_pin_current_chat(),
+            Command::CycleSort => self.cycle_sort(),
+            Command::ExportChat => {
+                if let Some(chat) = selected_chat {
+                    let path = format!("./{}.md", export::slug(&chat.title));
+                    self.open_modal(Modal::Export(ExportScope::SelectedChat), Some(path));
+                }
+            }
+            Command::ExportAllChats => {
+                let path = "./chats.md".to_string();
+                self.open_modal(Modal::Export(ExportScope::AllChats), Some(path));
+            }
+            Command::ImportChats => {
+                let path = "~/Downloads/conversations.json".to_string();
+                self.open_modal(Modal::Import, Some(path));
+            }
+            Command::Find => {
+                if self.active_chat_idx.is_some() {
+                    let query = self.search.query.clone();
+                    self.open_modal(Modal::Search, Some(query));
+                }
+            }
+            Command::NextMatch => self.next_match(),
+            Command::PrevMatch => self.prev_match(),
+            Command::DeleteMessage => self.delete_message(),
+            Command::ScrollUp
+            | Command::ScrollDown
+            | Command::ScrollPageUp
+            | Command::ScrollPageDown
+            | Command::ScrollHalfPageUp
+            | Command::ScrollHalfPageDown
+            | Command::ScrollTop
+            | Command::ScrollBottom => self.scroll_messages(command),
+            Command::ViewMessage => self.view_message(),
+            Command::ToggleWrap => {
+                if let Some(viewer) = &mut self.viewer {
+                    viewer.toggle_wrap();
+                }
+            }
+            Command::OpenInPager => self.open_in_pager(),
+            Command::YankMessage => self.yank_message(false),
+            Command::YankQuote => self.yank_message(true),
+            Command::YankCodeBlock => self.yank_code_block(None),
+            Command::SaveCodeBlock => self.pick_code_block(BlockAction::Save),
+            Command::ApplyPatch => self.review_patch(),
+            Command::RunCodeBlock => self.pick_code_block(BlockAction::Run),
+            Command::ToggleSidebar => self.toggle_sidebar(),
+            Command::GrowSidebar => self.resize_layout(|l| l.resize_sidebar(5)),
+            Command::ShrinkSidebar => self.resize_layout(|l| l.resize_sidebar(-5)),
+            Command::GrowInput => self.resize_layout(|l| l.resize_input(1)),
+            Command::ShrinkInput => self.resize_layout(|l| l.resize_input(-1)),
+            Command::ToggleZoom => self.layout.zoom = !self.layout.zoom,
+            Command::FocusChats => {
+                self.focus(Section::Chats);
+                self.select_current_chat();
+            }
+            Command::FocusMessages => self.focus(Section::Messages),
+            Command::FocusInput => self.focus(Section::Input),
+            Command::SwitchModel => {
+                self.open_modal(Modal::SwitchModel, Some(self.config.model.clone()))
+            }
+            Command::CycleTheme => self.cycle_theme(),
+            Command::OpenPalette => self.open_palette(),
+            Command::Quit => self.should_quit = true,
+        }
+    }
+
+    pub async fn dispatch(&mut self, action: Action) -> anyhow::Result<()> {
+        self.notice = None;
+
+        if let Action::Command(command) = action {
+            self.run_command(command);
+            return Ok(());
+        }
+
+        match &self.focus {
+            None => match self.section {
+                Section::Chats if matches!(action, Action::Enter) => {
+                    self.focus(Section::Chats);
+                    self.select_current_chat()
+                }
+                Section::Messages | Section::Input
+                    if matches!(action, Action::Esc | Action::Left | Action::Right) =>
+                {
+                    self.focus(Section::Chats);
+                    self.input.clear();
+                }
+                Section::Messages => match action {
+                    Action::Enter => self.focus(Section::Messages),
+                    Action::Up | Action::Down => self.section = Section::Input,
+                    _ => {}
+                },
+                Section::Input => match action {
+                    Action::Enter => self.focus(Section::Input),
+                    Action::Up | Action::Down => self.section = Section::Messages,
+                    _ => {}
+                },
+                _ => {}
+            },
+            Some(section) => match section {
+                Section::Modal if self.modal == Some(Modal::Palette) => match action {
+                    Action::Esc => self.close_palette(),
+                    Action::Enter => self.run_palette_selection(),
+                    Action::Up => self.palette.prev(),
+                    Action::Down => self.palette.next(),
+                    Action::Left => self.modal_input.left(),
+                    Action::Right => self.modal_input.right(),
+                    Action::Char(to_enter) => {
+                        self.modal_input.insert(to_enter);
+                        self.filter_palette();
+                    }
+                    Action::Backspace => {
+                        self.modal_input.delete();
+                        self.filter_palette();
+                    }
+                    _ => {}
+                },
+                Section::Modal if matches!(self.modal, Some(Modal::PickCodeBlock(_))) => {
+                    match (action, self.modal.clone()) {
+                        (Action::Esc, _) => {
+                            self.close_modal();
+                            self.focus_message_view();
+                        }
+                        (Action::Up, _) => self.code_blocks.prev(),
+                        (Action::Down, _) => self.code_blocks.next(),
+                        (Action::Enter, Some(Modal::PickCodeBlock(block_action))) => {
+                            self.close_modal();
+                            self.run_block_action(block_action);
+                        }
+                        _ => {}
+                    }
+                }
+                Section::Modal if self.modal == Some(Modal::ConfirmSave) => match action {
+                    Action::Enter | Action::Char('y') => self.confirm_save(),
+                    Action::Esc | Action::Char('n') => {
+                        self.pending_save = None;
+                        self.close_modal();
+                        self.focus_message_view();
+                    }
+                    Action::Up => self.preview_scroll.up(1),
+                    Action::Down => self.preview_scroll.down(1),
+                    _ => {}
+                },
+                Section::Modal if self.modal == Some(Modal::ConfirmCommand) => match action {
+                    Action::Enter | Action::Char('y') => {
+                        let has_output = self.pending_message.as_ref().is_some_and(|m| {
+                            m.attachments
+                                .iter()
+                                .any(|a| a.kind == AttachmentKind::Command)
+                        });
+
+                        match (has_output, self.command_running) {
+                            (true, _) => self.send_pending_message().await?,
+                            (false, false) => self.run_pending_command(),
+                            (false, true) => {}
+                        }
+                    }
+                    // back to the input to edit the command, a late output is dropped
+                    Action::Esc | Action::Char('n') => {
+                        self.pending_message = None;
+                        self.command_running = false;
+                        self.close_modal();
+                        self.focus(Section::Input);
+                    }
+                    Action::Up => self.preview_scroll.up(1),
+                    Action::Down => self.preview_scroll.down(1),
+                    _ => {}
+                },
+                Section::Modal if self.modal == Some(Modal::ApproveTool) => {
+                    // like code blocks, commands run only once `run` is typed
+                    let typed = self
+                        .tool_calls
+                        .as_ref()
+                        .and_then(|p| p.calls.front())
+                        .is_some_and(|call| call.name == "run_command");
+                    let running = self.tool_calls.as_ref().is_some_and(|p| p.running);
+
+                    match (action, typed) {
+                        (Action::Esc, _) => self.cancel_tool_calls(),
+                        (Action::Up, _) => self.preview_scroll.up(1),
+                        (Action::Down, _) => self.preview_scroll.down(1),
+                        _ if running => {}
+                        (Action::Enter, true) => match self.modal_input.text.trim() {
+                            "run" => self.answer_tool_call(true).await,
+                            "n" => self.answer_tool_call(false).await,
+                            _ => self.notice = Some("Type run to allow or n to deny".to_string()),
+                        },
+                        (Action::Char(c), true) => self.modal_input.insert(c),
+                        (Action::Backspace, true) => self.modal_input.delete(),
+                        (Action::Enter | Action::Char('y'), false) => {
+                            self.answer_tool_call(true).await
+                        }
+                        (Action::Char('n'), false) => self.answer_tool_call(false).await,
+                        _ => {}
+                    }
+                }
+                Section::Modal if self.modal == Some(Modal::ApplyPatch) => {
+                    let Some(review) = &mut self.patch_review else {
+                        return Ok(());
+                    };
+
+                    match action {
+                        Action::Enter | Action::Char('a') => self.apply_patch(),
+                        Action::Esc | Action::Char('q') => {
+                            self.patch_review = None;
+                            self.close_modal();
+                            self.focus_message_view();
+                        }
+                        Action::Up | Action::Char('k') => review.prev(),
+                        Action::Down | Action::Char('j') => review.next(),
+                        Action::Char(' ') => review.toggle(),
+                        Action::Char('y') => {
+                            review.set_accepted(true);
+                            review.next();
+                        }
+                        Action::Char('n') => {
+                            review.set_accepted(false);
+                            review.next();
+                        }
+                        _ => {}
+                    }
+                }
+                Section::Modal => match action {
+                    Action::Esc => {
+                        match self.modal {
+                            Some(Modal::RenameChat)
+                            | Some(Modal::NewChat)
+                            | Some(Modal::TagChat)
+                            | Some(Modal::MoveChat)
+                            | Some(Modal::FilterByTag)
+                            | Some(Modal::Export(_))
+                            | Some(Modal::Import) => {
+                                self.section = Section::Chats;
+                                self.focus = Some(Section::Chats);
+
+                                self.select_current_chat();
+                            }
+                            Some(Modal::Search) => self.focus(Section::Messages),
+                            Some(Modal::SwitchModel) => self.focus(Section::Input),
+                            Some(Modal::YankCodeBlock(_)) | Some(Modal::SaveCodeBlock) => {
+                                self.focus_message_view()
+                            }
+                            Some(Modal::ConfirmRun) => {
+                                self.pending_run = None;
+                                self.focus_message_view();
+                            }
+                            _ => {}
+                        };
+
+                        self.close_modal();
+                    }
+                    Action::Char(to_enter) => self.modal_input.insert(to_enter),
+                    Action::Backspace => self.modal_input.delete(),
+                    Action::Left => self.modal_input.left(),
+                    Action::Right => self.modal_input.right(),
+                    Action::Enter => match self.modal {
+                        Some(Modal::NewChat) => {
+                            let title = trim_spaces(&self.modal_input.text);
+
+                            match title.is_empty() {
+                                true => self.append_new_chat(),
+                                false => self.new_chat(&title, TitleSource::User),
+                            }
+
+                            self.close_modal();
+                            self.focus(Section::Chats);
+                        }
+                        Some(Modal::RenameChat) => {
+                            let title = &self.modal_input.text.clone();
+                            self.rename_current_chat(title);
+                            self.close_modal();
+                            self.focus(Section::Chats);
+                        }
+                        Some(Modal::TagChat) => {
+                            let tags = &self.modal_input.text.clone();
+                            self.tag_current_chat(tags);
+                            self.close_modal();
+                            self.focus(Section::Chats);
+                        }
+                        Some(Modal::MoveChat) => {
+                            let folder = &self.modal_input.text.clone();
+                            self.move_current_chat(folder);
+                            self.close_modal();
+                            self.focus(Section::Chats);
+                        }
+                        Some(Modal::FilterByTag) => {
+                            let tag = &self.modal_input.text.clone();
+                            self.set_tag_filter(tag);
+                            self.close_modal();
+                            self.focus(Section::Chats);
+                        }
+                        Some(Modal::Export(scope)) => {
+                            let path = self.modal_input.text.clone();
+                            self.export_chats(scope, &path);
+                            self.close_modal();
+                            self.focus(Section::Chats);
+                        }
+                        Some(Modal::Import) => {
+                            let path = self.modal_input.text.clone();
+                            self.import_chats(&path);
+                            self.close_modal();
+                            self.focus(Section::Chats);
+                        }
+                        Some(Modal::SwitchModel) => {
+                            let model = trim_spaces(&self.modal_input.text);
+
+                            if !model.is_empty() {
+                                self.notice = Some(format!("Switched model to {}", model));
+                                self.config.model = model;
+                            }
+
+                            self.close_modal();
+                            self.focus(Section::Input);
+                        }
+                        Some(Modal::Palette)
+                        | Some(Modal::PickCodeBlock(_))
+                        | Some(Modal::ConfirmSave)
+                        | Some(Modal::ApplyPatch)
+                        | Some(Modal::ConfirmCommand)
+                        | Some(Modal::ApproveTool) => {}
+                        Some(Modal::Search) => {
+                            let query = &self.modal_input.text.clone();
+                            self.find_in_chat(query);
+                            self.close_modal();
+                            self.focus(Section::Messages);
+                        }
+                        Some(Modal::ConfirmRun) => match self.modal_input.text.trim() {
+                            "run" => self.confirm_run(false),
+                            "send" => self.confirm_run(true),
+                            _ => self.notice = Some("Type run or send to confirm".to_string()),
+                        },
+                        Some(Modal::SaveCodeBlock) => {
+                            let path = self.modal_input.text.clone();
+                            self.close_modal();
+                            self.prepare_save(&path);
+                        }
+                        Some(Modal::YankCodeBlock(_)) => {
+                            let number = self.modal_input.text.trim().parse::<usize>().ok();
+                            self.close_modal();
+                            self.focus_message_view();
+
+                            match number {
+                                Some(number) => self.yank_code_block(Some(number)),
+                                None => self.notice = Some("Not a block number".to_string()),
+                            }
+                        }
+                        None => {}
+                    },
+                    _ => {}
+                },
+                Section::Chats => match action {
+                    Action::Up => self.sidebar_prev(),
+                    Action::Down => self.sidebar_next(),
+                    Action::Enter => self.open_sidebar_row(),
+                    Action::Esc => self.blur(),
+                    _ => {}
+                },
+                Section::Messages => {
+                    if let Some(chat) = self.get_active_chat_mut() {
+                        match action {
+                            Action::Up => {
+                                chat.messages.prev();
+                                chat.scroll.reveal = true;
+                            }
+                            Action::Down => {
+                                chat.messages.next();
+                                chat.scroll.reveal = true;
+                            }
+                            Action::Esc if self.search.is_active() => self.search.clear(),
+                            Action::Esc => self.blur(),
+                            _ => {}
+                        }
+                    } else {
+                        if action == Action::Esc {
+                            self.blur();
+                        }
+                    }
+                }
+                Section::Viewer => match (&mut self.viewer, action) {
+                    (Some(viewer), Action::Up) => viewer.scroll.up(1),
+                    (Some(viewer), Action::Down) => viewer.scroll.down(1),
+                    (Some(viewer), Action::Left) => viewer.left(),
+                    (Some(viewer), Action::Right) => viewer.right(),
+                    (_, Action::Esc) | (None, _) => self.close_viewer(),
+                    _ => {}
+                },
+                Section::Input => match action {
+                    Action::Char(to_enter) => self.input.insert(to_enter),
+                    Action::Key(KeyCode::Tab) => self.complete_input(),
+                    Action::Enter => self.submit_message().await?,
+                    Action::Backspace => self.input.delete(),
+                    Action::Left => self.input.left(),
+                    Action::Right => self.input.right(),
+                    Action::Esc => self.blur(),
+                    _ => {}
+                },
+            },
+        };
+
+        Ok(())
+    }
+
+    /// Opens the selected chat or toggles the selected folder
+    pub fn open_sidebar_row(&mut self) {
+        let row = self
+            .sidebar
+            .state
+            .selected()
+            .and_then(|i| self.sidebar.items.get(i).cloned());
+
+        match row {
+            Some(SidebarItem::Folder { name, .. }) => self.toggle_folder(&name),
+            Some(SidebarItem::Chat(chat_idx)) => {
+                self.active_chat_idx = Some(chat_idx);
+                self.search.clear();
+                self.focus(Section::Input);
+            }
+            Some(SidebarItem::Header(_)) | None => {}
+        }
+    }
+
+    /// Clicks focus the pane under the cursor, the wheel scrolls the list under it.
+    /// Ignored while a modal is open.
+    pub fn handle_mouse(&mut self, event: MouseEvent) {
+        if self.modal.is_some() {
+            return;
+        }
+
+        if let Some(viewer) = &mut self.viewer {
+            match event.kind {
+                MouseEventKind::ScrollUp => viewer.scroll.up(WHEEL_LINES),
+                MouseEventKind::ScrollDown => viewer.scroll.down(WHEEL_LINES),
+                _ => {}
+            }
+
+            return;
+        }
+
+        let (column, row) = (event.column, event.row);
+        let Some(section) = self.areas.section_at(column, row) else {
+            return;
+        };
+
+        match (event.kind, section) {
+            (MouseEventKind::Down(MouseButton::Left), Section::Chats) => {
+                self.notice = None;
+                self.focus(Section::Chats);
+
+                // the rows are from the last frame, the list may have changed since
+                if let Some(row) = self.areas.chat_row_at(column, row) {
+                    if self
+                        .sidebar
+                        .items
+                        .get(row)
+                        .is_some_and(|i| i.is_selectable())
+                    {
+                        self.sidebar.select(row);
+                        self.open_sidebar_row();
+                    }
+                }
+            }
+            (MouseEventKind::Down(MouseButton::Left), Section::Messages) => {
+                self.notice = None;
+                self.focus(Section::Messages);
+
+                let message = self.areas.message_at(column, row);
+
+                if let (Some(idx), Some(chat)) = (message, self.get_active_chat_mut()) {
+                    chat.messages.select(idx);
+                }
+            }
+            (MouseEventKind::Down(MouseButton::Left), section) => {
+                self.notice = None;
+                self.focus(section);
+            }
+            (MouseEventKind::ScrollUp, Section::Chats) => self.sidebar_prev(),
+            (MouseEventKind::ScrollDown, Section::Chats) => self.sidebar_next(),
+            (MouseEventKind::ScrollUp, Section::Messages) => {
+                if let Some(chat) = self.get_active_chat_mut() {
+                    chat.scroll.up(WHEEL_LINES);
+                }
+            }
+            (MouseEventKind::ScrollDown, Section::Messages) => {
+                if let Some(chat) = self.get_active_chat_mut() {
+                    chat.scroll.down(WHEEL_LINES);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Exports to `path`, the extension (.md, .json, .html) picks the format
+    pub fn export_chats(&mut self, scope: ExportScope, path: &str) {
+        let path = expand_home(path.trim());
+
+        let chats: Vec<Chat> = match scope {
+            ExportScope::SelectedChat => self
+                .selected_chat_idx()
+                .and_then(|i| self.chats.items.get(i))
+                .cloned()
+                .into_iter()
+                .collect(),
+            ExportScope::AllChats => self.chats.items.clone(),
+        };
+
+        if chats.is_empty() {
+            self.notice = Some("Nothing to export".to_string());
+            return;
+        }
+
+        self.notice = Some(match export::export(&chats, &path) {
+            Ok(()) => format!("Exported {} chat(s) to {}", chats.len(), path.display()),
+            Err(e) => format!("Export failed: {}", e),
+        });
+    }
+
+    /// Imports a ChatGPT web export (`conversations.json`)
+    pub fn import_chats(&mut self, path: &str) {
+        let path = expand_home(path.trim());
+
+        match import::import_conversations(&path) {
+            Ok(mut report) => {
+                report.skip_imported(&self.chats.items);
+                self.notice = Some(report.summary());
+
+                if !report.chats.is_empty() {
+                    self.chats.items.extend(report.chats);
+                    self.dirty = true;
+                    self.refresh_sidebar();
+                }
+            }
+            Err(e) => self.notice = Some(format!("Import failed: {}", e)),
+        }
+    }
+
+    pub fn get_active_chat_mut(&mut self) -> Option<&mut Chat> {
+        match self.active_chat_idx {
+            Some(index) => self.chats.items.get_mut(index),
+            None => None,
+        }
+    }
+
+    pub fn rename_current_chat(&mut self, title: &str) {
+        if let Some(i) = self.selected_chat_idx() {
+            if let Some(chat) = self.chats.items.get_mut(i) {
+                chat.set_title(title, TitleSource::User);
+                self.dirty = true;
+            }
+
+            self.select_chat_row(i);
+        }
+    }
+
+    pub fn blur(&mut self) {
+        self.focus = None;
+    }
+
+    pub fn find_in_chat(&mut self, query: &str) {
+        let Some(chat_idx) = self.active_chat_idx else {
+            return;
+        };
+
+        if let Some(chat) = self.chats.items.get(chat_idx) {
+            self.search.run(query, &chat.messages.items);
+        }
+
+        self.select_current_match();
+    }
+
+    pub fn next_match(&mut self) {
+        self.search.next();
+        self.select_current_match();
+    }
+
+    pub fn prev_match(&mut self) {
+        self.search.prev();
+        self.select_current_match();
+    }
+
+    /// Selects the message holding the current match and scrolls to it
+    fn select_current_match(&mut self) {
+        if let Some(message_idx) = self.search.current_match().map(|m| m.message_idx) {
+            if let Some(chat) = self.get_active_chat_mut() {
+                chat.messages.select(message_idx);
+                chat.scroll.reveal = true;
+            }
+        }
+    }
+
+    pub fn delete_message(&mut self) {
+        if let Some(chat) = self.get_active_chat_mut() {
+            if let Some(index) = chat.messages.state.selected() {
+                chat.messages.prev();
+                chat.messages.items.remove(index);
+                self.dirty = true;
+            }
+        }
+
+        self.refresh_search();
+    }
+
+    /// Re-runs the active search after the chat content changed
+    pub fn refresh_search(&mut self) {
+        if self.search.is_active() {
+            let query = self.search.query.clone();
+            let current = self.search.current;
+
+            if let Some(chat) = self.active_chat_idx.and_then(|i| self.chats.items.get(i)) {
+                self.search.run(&query, &chat.messages.items);
+                self.search.current = current.min(self.search.matches.len().saturating_sub(1));
+            }
+        }
+    }
+
+    pub async fn submit_message(&mut self) -> anyhow::Result<()> {
+        if self.input.is_empty() {
+            self.input.clear();
+            return Ok(());
+        }
+
+        let text = trim_spaces(&self.input.text.clone());
+
+        match slash::parse(&text) {
+            Some(Ok(command)) => {
+                self.input.clear();
+                return self.run_slash_command(command).await;
+            }
+            // keep the input so the command can be fixed
+            Some(Err(e)) => {
+                self.notice = Some(e);
+                return Ok(());
+            }
+            None => {}
+        }
+
+        // `//foo` sends `/foo`
+        let text = text.strip_prefix('/').unwrap_or(&text);
+        let mut message = Message::new(Role::User, text);
+
+        // the command is shown before it runs, then its output before sending
+        if let Some(command) = attach::command(text) {
+            match run::check_allowed(command, &self.config.run.allowlist) {
+                Ok(()) => {
+                    self.pending_message = Some(message);
+                    self.command_running = false;
+                    self.open_modal(Modal::ConfirmCommand, None);
+                }
+                Err(e) => self.notice = Some(format!("Not run: {}", e)),
+            }
+
+            return Ok(());
+        }
+
+        message.attachments = match attach::resolve(text, &self.config.attachments) {
+            Ok(attachments) => attachments,
+            // keep the input so the mention can be fixed
+            Err(e) => {
+                self.notice = Some(format!("Not sent: {}", e));
+                return Ok(());
+            }
+        };
+
+        self.input.clear();
+
+        if let Some(chat) = self.get_active_chat_mut() {
+            chat.append_message(message);
+            self.send_active_chat().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Answers the first pending tool call, running it when `allow` is set. Once
+    /// every call has a result, they go back to the model.
+    /// Runs the first pending call in the background, its result comes back as
+    /// `AppEvent::ToolResult`. A denied call is answered right away.
+    async fn answer_tool_call(&mut self, allow: bool) {
+        let events = self.events.clone();
+        let Some(pending) = self.tool_calls.as_mut().filter(|p| !p.running) else {
+            return;
+        };
+
+        let Some(call) = pending.calls.front().cloned() else {
+            return;
+        };
+
+        let chat_id = pending.chat_id.clone();
+        self.modal_input.clear();
+
+        match (allow, events) {
+            (true, Some(events)) => {
+                pending.running = true;
+                self.loading = true;
+                let config = self.config.clone();
+
+                tokio::spawn(async move {
+                    let result = tools::call(&call, &config).await;
+                    let _ = events.send(AppEvent::ToolResult {
+                        chat_id,
+                        call_id: call.id,
+                        result,
+                    });
+                });
+            }
+            (true, None) => {
+                let result = tools::call(&call, &self.config).await;
+                self.finish_tool_call(&call.id, &result).await;
+            }
+            (false, _) => {
+                self.finish_tool_call(&call.id, "The user denied this tool call.")
+                    .await
+            }
+        }
+    }
+
+    /// Answers the first pending call with `result`, the chat goes back to the
+    /// model once every call has one
+    async fn finish_tool_call(&mut self, call_id: &str, result: &str) {
+        let Some(pending) = self.tool_calls.as_mut() else {
+            return;
+        };
+
+        pending.calls.pop_front();
+        pending.running = false;
+
+        let chat_id = pending.chat_id.clone();
+        let done = pending.calls.is_empty();
+
+        self.append_tool_result(&chat_id, call_id, result);
+        self.preview_scroll.top();
+
+        if done {
+            self.tool_calls = None;
+            self.close_modal();
+            self.focus(Section::Input);
+
+            // a failed request must not take the app down with it
+            if let Some(chat_idx) = self.chats.items.iter().position(|c| c.id == chat_id) {
+                if let Err(e) = self.send_chat(chat_idx).await {
+                    self.loading = false;
+                    self.notice = Some(format!("Sending failed: {}", e));
+                }
+            }
+        }
+    }
+
+    /// Denies the remaining calls without going back to the model. Every call
+    /// still needs a result for the next request to be accepted.
+    fn cancel_tool_calls(&mut self) {
+        if let Some(pending) = self.tool_calls.take() {
+            // the running call finishes unseen, `AppEvent::ToolResult` drops it
+            if pending.running {
+                self.loading = false;
+            }
+
+            for call in pending.calls {
+                self.append_tool_result(
+                    &pending.chat_id,
+                    &call.id,
+                    "The user cancelled this tool call.",
+                );
+            }
+        }
+
+        self.close_modal();
+        self.focus(Section::Input);
+    }
+
+    fn append_tool_result(&mut self, chat_id: &ID, call_id: &str, result: &str) {
+        if let Some(chat) = self.chats.items.iter_mut().find(|c| &c.id == chat_id) {
+            chat.append_message(Message::tool(call_id, result));
+            self.dirty = true;
+        }
+    }
+
+    /// Runs the `!command` of `App::pending_message` in the background, its
+    /// output comes back as `AppEvent::CommandOutput`
+    fn run_pending_command(&mut self) {
+        let (Some(message), Some(events)) = (&self.pending_message, self.events.clone()) else {
+            return;
+        };
+
+        let Some(command) = attach::command(&message.content).map(str::to_string) else {
+            return;
+        };
+
+        let message_id = message.id.clone();
+        let config = self.config.run.clone();
+        self.command_running = true;
+
+        tokio::spawn(async move {
+            let output = attach::command_output(&command, &config)
+                .await
+                .map_err(|e| format!("`{}` {}", command, e));
+
+            let _ = events.send(AppEvent::CommandOutput { message_id, output });
+        });
+    }
+
+    /// Sends the message previewed in `Modal::ConfirmCommand`
+    async fn send_pending_message(&mut self) -> anyhow::Result<()> {
+        self.close_modal();
+        self.focus(Section::Input);
+
+        let Some(message) = self.pending_message.take() else {
+            return Ok(());
+        };
+
+        self.input.clear();
+
+        if let Some(chat) = self.get_active_chat_mut() {
+            chat.append_message(message);
+            self.send_active_chat().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends the active chat and appends the answer
+    async fn send_active_chat(&mut self) -> anyhow::Result<()> {
+        if let Some(active_chat_index) = self.active_chat_idx {
+            self.send_chat(active_chat_index).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a chat and appends the answer. Tool calls in the answer are asked
+    /// for approval, their results are sent back in turn.
+    async fn send_chat(&mut self, chat_idx: usize) -> anyhow::Result<()> {
+        self.loading = true;
+
+        if let Some(chat) = self.chats.items.get_mut(chat_idx) {
+            attach::load_all(chat, &self.config.attachments);
+
+            let answer =
+                send_message(chat.clone(), &self.config.model, self.config.tools.enabled).await?;
+
+            if let Some(m) = answer {
+                if !m.tool_calls.is_empty() {
+                    self.tool_calls = Some(PendingCalls {
+                        chat_id: chat.id.clone(),
+                        calls: m.tool_calls.clone().into(),
+                        running: false,
+                    });
+                }
+
+                chat.append_message(m);
+            }
+
+            self.dirty = true;
+        }
+
+        if self.tool_calls.is_some() {
+            self.preview_scroll = Scroll {
+                follow: false,
+                ..Scroll::default()
+            };
+            self.open_modal(Modal::ApproveTool, None);
+        }
+
+        self.request_title(chat_idx);
+        self.refresh_sidebar();
+        self.refresh_search();
+
+        self.loading = false;
+
+        Ok(())
+    }
+
+    pub fn complete_input(&mut self) {
+        let completion = slash::completion(&self.input.text).or_else(|| {
+            let (suffix, entries) = attach::completion(&self.input.text)?;
+
+            if entries.len() > 1 {
+                self.notice = Some(entries.join("  "));
+            }
+
+            Some(suffix)
+        });
+
+        if let Some(completion) = completion.filter(|c| !c.is_empty()) {
+            let mut text = self.input.text.clone();
+            text.push_str(&completion);
+            self.input.set_value(text);
+        }
+    }
+
+    pub async fn run_slash_command(&mut self, command: SlashCommand) -> anyhow::Result<()> {
+        // model switching is global, everything else needs an open chat
+        if let SlashCommand::Model(model) = command {
+            self.notice = Some(format!("Switched model to {}", model));
+            self.config.model = model;
+            return Ok(());
+        }
+
+        // the transcript goes back to the input for editing
+        if let SlashCommand::Transcribe(path) = command {
+            self.loading = true;
+            let transcript = transcribe(&path, &self.config.transcription).await;
+            self.loading = false;
+
+            match transcript {
+                Ok(text) if text.len() > self.input.max_length => {
+                    self.notice = Some(format!(
+                        "Transcript cut to the input limit of {} bytes",
+                        self.input.max_length
+                    ));
+                    self.input.set_value(text);
+                }
+                Ok(text) => self.input.set_value(text),
+                Err(e) => self.notice = Some(format!("Transcription failed: {}", e)),
+            }
+
+            return Ok(());
+        }
+
+        let Some(chat_idx) = self.active_chat_idx else {
+            self.notice = Some("Open a chat first".to_string());
+            return Ok(());
+        };
+
+        let Some(chat) = self.chats.items.get_mut(chat_idx) else {
+            return Ok(());
+        };
+
+        match command {
+            SlashCommand::Model(_) | SlashCommand::Transcribe(_) => {}
+            SlashCommand::System(prompt) => {
+                chat.system_prompt = Some(prompt);
+                self.notice = Some("System prompt set".to_string());
+            }
+            SlashCommand::Clear => {
+                chat.messages.items.clear();
+                chat.messages.unselect();
+                self.search.clear();
+            }
+            SlashCommand::Title(title) => chat.set_title(&title, TitleSource::User),
+            SlashCommand::Retry => {
+                if matches!(chat.messages.items.last(), Some(m) if matches!(m.role, Role::Assistant))
+                {
+                    chat.remove_last_message();
+                }
+
+                match chat.messages.items.last() {
+                    Some(m) if matches!(m.role, Role::User) => self.send_active_chat().await?,
+                    _ => self.notice = Some("Nothing to retry".to_string()),
+                }
+            }
+            SlashCommand::Export(format) => {
+                let path = format!("./{}.{}", export::slug(&chat.title), format.extension());
+                let chats = vec![chat.clone()];
+
+                self.notice = Some(match export::export(&chats, &expand_home(&path)) {
+                    Ok(()) => format!("Exported chat to {}", path),
+                    Err(e) => format!("Export failed: {}", e),
+                });
+            }
+        }
+
+        self.dirty = true;
+        self.refresh_sidebar();
+
+        Ok(())
+    }
+
+    /// Generates a title in the background once the first exchange is done
+    fn request_title(&mut self, chat_idx: usize) {
+        if !self.config.auto_title.enabled {
+            return;
+        }
+
+        let (Some(chat), Some(events)) = (self.chats.items.get(chat_idx), self.events.clone())
+        else {
+            return;
+        };
+
+        if !chat.needs_title() || self.titling.contains(&chat.id) {
+            return;
+        }
+
+        let chat = chat.clone();
+        let config = self.config.auto_title.clone();
+        self.titling.insert(chat.id.clone());
+
+        tokio::spawn(async move {
+            let title = match generate_title(chat.clone(), config).await {
+                Ok(title) => title,
+                Err(e) => {
+                    log::warn!("failed to generate a title: {}", e);
+                    None
+                }
+            };
+
+            let _ = events.send(AppEvent::ChatTitled {
+                chat_id: chat.id,
+                title,
+            });
+        });
+    }
+
+    pub async fn handle_event(&mut self, event: AppEvent) -> anyhow::Result<()> {
+        match event {
+            AppEvent::ChatTitled { chat_id, title } => {
+                self.titling.remove(&chat_id);
+
+                let chat = self.chats.items.iter_mut().find(|c| c.id == chat_id);
+
+                // the user may have renamed it while the request was in flight
+                if let (Some(chat), Some(title)) =
+                    (chat.filter(|c| c.title_source != TitleSource::User), title)
+                {
+                    chat.set_title(&title, TitleSource::Generated);
+                    self.dirty = true;
+                    self.refresh_sidebar();
+                }
+            }
+            AppEvent::RunOutput {
+                chat_id,
+                message_id,
+                text,
+            } => {
+                if let Some(message) = self.find_message_mut(&chat_id, &message_id) {
+                    message.content.push_str(&text);
+                }
+            }
+            AppEvent::CommandOutput { message_id, output } => {
+                let Some(message) = self
+                    .pending_message
+                    .as_mut()
+                    .filter(|m| m.id == message_id && self.command_running)
+                else {
+                    return Ok(());
+                };
+
+                self.command_running = false;
+
+                match output {
+                    Ok(output) => {
+                        message.attachments.push(output);
+                        self.preview_scroll = Scroll {
+                            follow: false,
+                            ..Scroll::default()
+                        };
+                    }
+                    Err(e) => {
+                        self.pending_message = None;
+                        self.close_modal();
+                        self.focus(Section::Input);
+                        self.notice = Some(format!("Not sent: {}", e));
+                    }
+                }
+            }
+            AppEvent::ToolResult {
+                chat_id,
+                call_id,
+                result,
+            } => {
+                // the calls may have been cancelled while this one ran
+                let current = self.tool_calls.as_ref().is_some_and(|p| {
+                    p.running
+                        && p.chat_id == chat_id
+                        && p.calls.front().is_some_and(|call| call.id == call_id)
+                });
+
+                if current {
+                    self.loading = false;
+                    self.finish_tool_call(&call_id, &result).await;
+                }
+            }
+            AppEvent::RunFinished {
+                chat_id,
+                message_id,
+                status,
+                send,
+            } => {
+                if let Some(message) = self.find_message_mut(&chat_id, &message_id) {
+                    message.content.push_str(&format!("```\n{}", status));
+                }
+
+                self.dirty = true;
+                self.notice = Some(format!("Command finished, {}", status));
+                self.refresh_search();
+
+                let chat_idx = self.chats.items.iter().position(|c| c.id == chat_id);
+
+                // a failed request must not take the app down with it
+                if let (true, Some(chat_idx)) = (send, chat_idx) {
+                    if let Err(e) = self.send_chat(chat_idx).await {
+                        self.loading = false;
+                        self.notice = Some(format!("Sending failed: {}", e));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn find_message_mut(&mut self, chat_id: &ID, message_id: &ID) -> Option<&mut Message> {
+        self.chats
+            .items
+            .iter_mut()
+            .find(|c| &c.id == chat_id)?
+            .messages
+            .items
+            .iter_mut()
+            .find(|m| &m.id == message_id)
+    }
+
+    pub fn append_new_chat(&mut self) {
+        self.new_chat(
+            &format!("chat #{}", self.chats.items.len()),
+            TitleSource::Default,
+        );
+    }
+
+    pub fn new_chat(&mut self, title: &str, source: TitleSource) {
+        let mut chat = Chat::new(title);
+        chat.title_source = source;
+
+        self.chats.items.push(chat);
+        self.dirty = true;
+        self.refresh_sidebar();
+
+        let chat_idx = self.chats.items.len() - 1;
+
+        // a brand new chat has no tags, so drop the filter that would hide it
+        if !self.select_chat_row(chat_idx) {
+            self.tag_filter = None;
+            self.refresh_sidebar();
+            self.select_chat_row(chat_idx);
+        }
+    }
+
+    pub fn delete_current_chat(&mut self) {
+        if let Some(i) = self.selected_chat_idx() {
+            let row = self.sidebar.state.selected().unwrap_or(0);
+            self.chats.items.remove(i);
+            self.dirty = true;
+
+            self.active_chat_idx = match self.active_chat_idx {
+                Some(active) if active == i => None,
+                Some(active) if active > i => Some(active - 1),
+                active => active,
+            };
+
+            self.refresh_sidebar();
+
+            // the row of the deleted chat, not where its index now points
+            if !self.sidebar.items.is_empty() {
+                self.sidebar.select(row.min(self.sidebar.items.len() - 1));
+                self.skip_sidebar_headers(true);
+                self.sidebar_prev();
+            }
         }
     }
 }
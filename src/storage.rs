@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::PaneLayout;
+use crate::models::Chat;
+use crate::sidebar::SortBy;
+
+/// Everything that survives a restart
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedState {
+    #[serde(default)]
+    pub chats: Vec<Chat>,
+    #[serde(default)]
+    pub collapsed_folders: Vec<String>,
+    #[serde(default)]
+    pub sort_by: SortBy,
+    #[serde(default)]
+    pub layout: PaneLayout,
+    /// Theme picked with the theme switcher, used unless the config sets one
+    #[serde(default)]
+    pub theme: Option<String>,
+}
+
+/// `$XDG_DATA_HOME/chatgpt-tui`, falling back to `~/.local/share/chatgpt-tui`
+pub fn data_dir() -> PathBuf {
+    let base = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir().join(".local").join("share"),
+    };
+
+    base.join("chatgpt-tui")
+}
+
+pub fn home_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Expands a leading `~/` to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => home_dir().join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+fn state_file() -> PathBuf {
+    data_dir().join("chats.json")
+}
+
+fn lock_file() -> PathBuf {
+    data_dir().join("chats.lock")
+}
+
+/// Held by the interactive UI while it runs, the file goes away on drop
+pub struct Lock(PathBuf);
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Marks the chats as open in the interactive UI, so that `ask --chat` does
+/// not save over them
+pub fn lock() -> anyhow::Result<Lock> {
+    let path = lock_file();
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(&path, std::process::id().to_string())?;
+
+    Ok(Lock(path))
+}
+
+/// Pid of the interactive UI holding the chats. A lock left behind by a crash
+/// is ignored.
+pub fn locked_by() -> Option<libc::pid_t> {
+    let pid: libc::pid_t = fs::read_to_string(lock_file()).ok()?.trim().parse().ok()?;
+
+    // SAFETY: signal 0 only checks that the process exists
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+
+    alive.then_some(pid)
+}
+
+pub fn load() -> anyhow::Result<SavedState> {
+    let path = state_file();
+
+    if !path.exists() {
+        return Ok(SavedState::default());
+    }
+
+    let content = fs::read_to_string(path)?;
+
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Like `load`, but a file that does not parse is moved aside and an empty
+/// state returned with a warning, so a bad file does not lock the user out
+pub fn load_or_recover() -> anyhow::Result<(SavedState, Option<String>)> {
+    let path = state_file();
+
+    if !path.exists() {
+        return Ok((SavedState::default(), None));
+    }
+
+    let content = fs::read_to_string(&path)?;
+
+    match serde_json::from_str(&content) {
+        Ok(state) => Ok((state, None)),
+        Err(e) => {
+            let backup = path.with_extension(format!(
+                "json.{}.bak",
+                chrono::Local::now().format("%Y%m%d-%H%M%S")
+            ));
+            fs::rename(&path, &backup)?;
+
+            let warning = format!(
+                "Could not read the saved chats ({}), moved them to {}",
+                e,
+                backup.display()
+            );
+
+            Ok((SavedState::default(), Some(warning)))
+        }
+    }
+}
+
+pub fn save(state: &SavedState) -> anyhow::Result<()> {
+    let path = state_file();
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // write to a temp file first so a crash never leaves a truncated history
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(state)?)?;
+    fs::rename(tmp, path)?;
+
+    Ok(())
+}
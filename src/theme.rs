@@ -0,0 +1,266 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Deserializer};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct StyleDef {
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    italic: bool,
+    dim: bool,
+    underlined: bool,
+    reversed: bool,
+}
+
+/// `{ fg = "yellow", bg = "#1e1e2e", bold = true }`
+fn style<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Style, D::Error> {
+    let def = StyleDef::deserialize(deserializer)?;
+
+    let color = |c: &Option<String>| -> Result<Option<Color>, D::Error> {
+        c.as_deref()
+            .map(|c| {
+                Color::from_str(c)
+                    .map_err(|_| serde::de::Error::custom(format!("invalid color `{}`", c)))
+            })
+            .transpose()
+    };
+
+    let mut style = Style::default();
+
+    if let Some(fg) = color(&def.fg)? {
+        style = style.fg(fg);
+    }
+
+    if let Some(bg) = color(&def.bg)? {
+        style = style.bg(bg);
+    }
+
+    let modifiers = [
+        (def.bold, Modifier::BOLD),
+        (def.italic, Modifier::ITALIC),
+        (def.dim, Modifier::DIM),
+        (def.underlined, Modifier::UNDERLINED),
+        (def.reversed, Modifier::REVERSED),
+    ];
+
+    for (enabled, modifier) in modifiers {
+        if enabled {
+            style = style.add_modifier(modifier);
+        }
+    }
+
+    Ok(style)
+}
+
+/// Every style used by the UI. Theme files only need the keys they change,
+/// the rest comes from the dark theme.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    #[serde(default)]
+    pub name: String,
+    #[serde(deserialize_with = "style")]
+    pub border: Style,
+    #[serde(deserialize_with = "style")]
+    pub border_hovered: Style,
+    #[serde(deserialize_with = "style")]
+    pub border_focused: Style,
+    /// Selected row in the chats list and the command palette
+    #[serde(deserialize_with = "style")]
+    pub highlight: Style,
+    #[serde(deserialize_with = "style")]
+    pub message_highlight: Style,
+    #[serde(deserialize_with = "style")]
+    pub user_message: Style,
+    #[serde(deserialize_with = "style")]
+    pub assistant_message: Style,
+    #[serde(deserialize_with = "style")]
+    pub code_block: Style,
+    #[serde(deserialize_with = "style")]
+    pub diff_added: Style,
+    #[serde(deserialize_with = "style")]
+    pub diff_removed: Style,
+    /// Markdown headings in the message viewer
+    #[serde(deserialize_with = "style")]
+    pub heading: Style,
+    #[serde(deserialize_with = "style")]
+    pub search_match: Style,
+    /// The match reached with next / previous match
+    #[serde(deserialize_with = "style")]
+    pub search_current: Style,
+    /// Secondary text: previews, tags, group headers, hints
+    #[serde(deserialize_with = "style")]
+    pub muted: Style,
+    #[serde(deserialize_with = "style")]
+    pub folder: Style,
+    #[serde(deserialize_with = "style")]
+    pub input_focused: Style,
+    #[serde(deserialize_with = "style")]
+    pub help_key: Style,
+    #[serde(deserialize_with = "style")]
+    pub notice: Style,
+    #[serde(deserialize_with = "style")]
+    pub modal_border: Style,
+    #[serde(deserialize_with = "style")]
+    pub modal_title: Style,
+    #[serde(deserialize_with = "style")]
+    pub modal_text: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            name: "dark".to_string(),
+            border: Style::default(),
+            border_hovered: Style::new().fg(Color::Yellow),
+            border_focused: Style::new().fg(Color::Green).add_modifier(Modifier::BOLD),
+            highlight: Style::new()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            message_highlight: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            user_message: Style::default(),
+            assistant_message: Style::default(),
+            code_block: Style::new().fg(Color::LightCyan),
+            heading: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            diff_added: Style::new().fg(Color::Green),
+            diff_removed: Style::new().fg(Color::Red),
+            search_match: Style::new().bg(Color::Magenta).fg(Color::Black),
+            search_current: Style::new()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            muted: Style::new().add_modifier(Modifier::DIM),
+            folder: Style::new().add_modifier(Modifier::BOLD),
+            input_focused: Style::new().fg(Color::Green),
+            help_key: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            notice: Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            modal_border: Style::new().fg(Color::Green).add_modifier(Modifier::BOLD),
+            modal_title: Style::new().fg(Color::White).add_modifier(Modifier::BOLD),
+            modal_text: Style::new().fg(Color::Yellow),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            name: "light".to_string(),
+            border: Style::new().fg(Color::Gray),
+            border_hovered: Style::new().fg(Color::Blue),
+            border_focused: Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            highlight: Style::new()
+                .bg(Color::Blue)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+            message_highlight: Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD),
+            user_message: Style::new().fg(Color::Black),
+            assistant_message: Style::new().fg(Color::Black),
+            code_block: Style::new().fg(Color::Red),
+            heading: Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD),
+            diff_added: Style::new().fg(Color::Green),
+            diff_removed: Style::new().fg(Color::Red),
+            search_match: Style::new().bg(Color::LightYellow).fg(Color::Black),
+            search_current: Style::new()
+                .bg(Color::LightRed)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            muted: Style::new().fg(Color::DarkGray),
+            folder: Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD),
+            input_focused: Style::new().fg(Color::Magenta),
+            help_key: Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD),
+            notice: Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            modal_border: Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            modal_title: Style::new().fg(Color::Black).add_modifier(Modifier::BOLD),
+            modal_text: Style::new().fg(Color::Blue),
+        }
+    }
+
+    /// Same theme with every color removed, see <https://no-color.org>
+    pub fn without_colors(&self) -> Self {
+        let strip = |style: Style| Style {
+            fg: None,
+            bg: None,
+            underline_color: None,
+            ..style
+        };
+
+        Self {
+            name: self.name.clone(),
+            border: strip(self.border),
+            border_hovered: strip(self.border_hovered),
+            border_focused: strip(self.border_focused).add_modifier(Modifier::BOLD),
+            // without colors the selection must still stand out
+            highlight: strip(self.highlight).add_modifier(Modifier::REVERSED),
+            message_highlight: strip(self.message_highlight).add_modifier(Modifier::REVERSED),
+            user_message: strip(self.user_message),
+            assistant_message: strip(self.assistant_message),
+            code_block: strip(self.code_block),
+            heading: strip(self.heading),
+            diff_added: strip(self.diff_added),
+            diff_removed: strip(self.diff_removed).add_modifier(Modifier::CROSSED_OUT),
+            search_match: strip(self.search_match).add_modifier(Modifier::UNDERLINED),
+            search_current: strip(self.search_current).add_modifier(Modifier::REVERSED),
+            muted: strip(self.muted),
+            folder: strip(self.folder),
+            input_focused: strip(self.input_focused),
+            help_key: strip(self.help_key),
+            notice: strip(self.notice),
+            modal_border: strip(self.modal_border),
+            modal_title: strip(self.modal_title),
+            modal_text: strip(self.modal_text),
+        }
+    }
+}
+
+pub fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+/// Built-in themes followed by every `*.toml` in `dir`, plus the files that failed to load
+pub fn load_themes(dir: &Path) -> (Vec<Theme>, Vec<String>) {
+    let mut themes = vec![Theme::dark(), Theme::light()];
+    let mut errors = Vec::new();
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return (themes, errors);
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let theme = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| toml::from_str::<Theme>(&content).map_err(|e| e.to_string()));
+
+        match theme {
+            Ok(mut theme) => {
+                // unnamed themes are named after their file
+                if theme.name.is_empty() {
+                    if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                        theme.name = stem.to_string();
+                    }
+                }
+
+                themes.retain(|t| t.name != theme.name);
+                themes.push(theme);
+            }
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    (themes, errors)
+}
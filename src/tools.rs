@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::attach;
+use crate::config::Config;
+use crate::models::{ToolCall, ID};
+use crate::run;
+use crate::utils::checked_path;
+
+/// Lines returned by `grep` before giving up
+const MAX_MATCHES: usize = 100;
+/// Directories `grep` does not descend into, besides hidden ones
+const SKIPPED_DIRS: [&str; 2] = ["target", "node_modules"];
+
+/// Calls of an answer, approved one at a time
+#[derive(Debug, Clone)]
+pub struct PendingCalls {
+    pub chat_id: ID,
+    pub calls: VecDeque<ToolCall>,
+    /// The first call was allowed and runs in the background
+    pub running: bool,
+}
+
+/// The tools offered to the model, in the chat completions format
+pub fn definitions() -> Value {
+    let tool = |name: &str, description: &str, parameters: Value| {
+        json!({
+            "type": "function",
+            "function": {
+                "name": name,
+                "description": description,
+                "parameters": parameters,
+            },
+        })
+    };
+
+    let string = |description: &str| json!({ "type": "string", "description": description });
+
+    json!([
+        tool(
+            "read_file",
+            "Read a text file of the user's project",
+            json!({
+                "type": "object",
+                "properties": { "path": string("Path relative to the project root") },
+                "required": ["path"],
+            }),
+        ),
+        tool(
+            "list_dir",
+            "List the entries of a directory, directories end with /",
+            json!({
+                "type": "object",
+                "properties": { "path": string("Directory, the project root by default") },
+            }),
+        ),
+        tool(
+            "grep",
+            "Search files for a regular expression, returns path:line: text",
+            json!({
+                "type": "object",
+                "properties": {
+                    "pattern": string("Regular expression"),
+                    "path": string("File or directory to search, the project root by default"),
+                },
+                "required": ["pattern"],
+            }),
+        ),
+        tool(
+            "run_command",
+            "Run a shell command in the project root, returns its output and exit status",
+            json!({
+                "type": "object",
+                "properties": { "command": string("Command for sh -c") },
+                "required": ["command"],
+            }),
+        ),
+    ])
+}
+
+/// Runs an approved call. Failures are reported to the model as the result.
+pub async fn call(call: &ToolCall, config: &Config) -> String {
+    let mut result = match run_tool(call, config).await {
+        Ok(result) => result,
+        Err(e) => format!("error: {}", e),
+    };
+
+    if result.len() > config.tools.max_result {
+        let mut end = config.tools.max_result;
+        while !result.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        result.truncate(end);
+        result.push_str("\n[truncated]");
+    }
+
+    result
+}
+
+async fn run_tool(call: &ToolCall, config: &Config) -> anyhow::Result<String> {
+    let args: Value = serde_json::from_str(&call.arguments)
+        .map_err(|e| anyhow::anyhow!("invalid arguments: {}", e))?;
+    let arg = |name: &str| args.get(name).and_then(Value::as_str);
+    let required = |name: &str| arg(name).ok_or_else(|| anyhow::anyhow!("missing `{}`", name));
+
+    // the model only gets to see the project
+    let path = |name: &str, default: Option<&str>| {
+        let path = arg(name)
+            .or(default)
+            .ok_or_else(|| anyhow::anyhow!("missing `{}`", name))?;
+        checked_path(path)
+    };
+
+    match call.name.as_str() {
+        "read_file" => attach::read_file(
+            &path("path", None)?.to_string_lossy(),
+            config.attachments.max_file_size,
+        ),
+        "list_dir" => list_dir(&path("path", Some("."))?),
+        "grep" => {
+            let re = Regex::new(required("pattern")?)?;
+            let path = path("path", Some("."))?;
+            let max_size = config.attachments.max_file_size;
+
+            // walking the tree is blocking IO, keep it off the runtime threads
+            tokio::task::spawn_blocking(move || grep(&re, &path, max_size)).await?
+        }
+        "run_command" => {
+            let command = required("command")?;
+            run::check_allowed(command, &config.run.allowlist)?;
+
+            let (output, status) = run::capture(command, &config.run).await?;
+            Ok(format!("{}[{}]", output, status))
+        }
+        name => anyhow::bail!("unknown tool `{}`", name),
+    }
+}
+
+fn list_dir(path: &Path) -> anyhow::Result<String> {
+    let mut entries: Vec<String> = fs::read_dir(path)
+        .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+
+            match entry.file_type().ok()?.is_dir() {
+                true => Some(format!("{}/", name)),
+                false => Some(name),
+            }
+        })
+        .collect();
+
+    entries.sort();
+
+    Ok(entries.join("\n"))
+}
+
+fn grep(re: &Regex, path: &Path, max_size: usize) -> anyhow::Result<String> {
+    let mut matches = Vec::new();
+
+    grep_path(path, re, max_size, &mut matches);
+
+    if matches.is_empty() {
+        return Ok("no matches".to_string());
+    }
+
+    if matches.len() > MAX_MATCHES {
+        matches.truncate(MAX_MATCHES);
+        matches.push(format!("[more than {} matches]", MAX_MATCHES));
+    }
+
+    Ok(matches.join("\n"))
+}
+
+/// Binary, unreadable and too large files are skipped, so are symlinks, which
+/// could lead out of the project or into a loop
+fn grep_path(path: &Path, re: &Regex, max_size: usize, matches: &mut Vec<String>) {
+    if matches.len() > MAX_MATCHES {
+        return;
+    }
+
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return;
+    };
+
+    if metadata.is_symlink() {
+        return;
+    }
+
+    if metadata.is_dir() {
+        let Ok(entries) = fs::read_dir(path) else {
+            return;
+        };
+
+        let mut entries: Vec<_> = entries.filter_map(|e| e.ok()).collect();
+        entries.sort_by_key(|e| e.file_name());
+
+        for entry in entries {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+
+            if name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref()) {
+                continue;
+            }
+
+            grep_path(&entry.path(), re, max_size, matches);
+        }
+
+        return;
+    }
+
+    let Ok(content) = attach::read_file(&path.to_string_lossy(), max_size) else {
+        return;
+    };
+
+    let name = path.strip_prefix("./").unwrap_or(path).display();
+
+    for (number, line) in content.lines().enumerate() {
+        if re.is_match(line) {
+            matches.push(format!("{}:{}: {}", name, number + 1, line.trim_end()));
+        }
+    }
+}
@@ -1,7 +1,20 @@
+use crate::attach;
+use crate::commands::Command;
 use crate::components::*;
+use crate::keymap::KeyContext;
+use crate::layout;
 use crate::models::*;
+use crate::mouse::{list_rows, Areas};
+use crate::patch::HunkLine;
+use crate::sidebar::SidebarItem;
+use crate::slash;
 use crate::state::*;
+use crate::theme::Theme;
 use crate::utils::*;
+use crate::viewer;
+
+use regex::Regex;
+use std::ops::Range;
 
 use ratatui::{
     layout::{Direction, Layout},
@@ -11,97 +24,432 @@ use ratatui::{
 };
 
 fn render_chats(f: &mut Frame, app: &mut App, area: Rect) {
+    let theme = &app.theme;
+    // borders + highlight symbol
+    let preview_width = area.width.saturating_sub(6) as usize;
+
     let chats: Vec<ListItem> = app
-        .chats
+        .sidebar
         .items
         .iter()
-        .map(|chat| {
-            let lines: Vec<Line> = vec![chat.title.clone().into()];
+        .map(|item| {
+            let lines: Vec<Line> = match item {
+                SidebarItem::Header(label) => {
+                    vec![Line::styled(label.clone(), theme.muted.italic())]
+                }
+                SidebarItem::Folder {
+                    name,
+                    collapsed,
+                    count,
+                } => {
+                    let icon = if *collapsed { "▸" } else { "▾" };
+
+                    vec![Line::from(vec![
+                        Span::styled(format!("{} {}", icon, name), theme.folder),
+                        Span::styled(format!(" ({})", count), theme.muted),
+                    ])]
+                }
+                SidebarItem::Chat(idx) => {
+                    let chat = &app.chats.items[*idx];
+                    let indent = if chat.folder.is_some() && !chat.pinned {
+                        "  "
+                    } else {
+                        ""
+                    };
+                    let pin = if chat.pinned { "⚑ " } else { "" };
+
+                    let mut spans = vec![Span::raw(format!("{}{}{}", indent, pin, chat.title))];
+                    spans.extend(
+                        chat.tags
+                            .iter()
+                            .map(|tag| Span::styled(format!(" #{}", tag), theme.muted)),
+                    );
+
+                    let preview: String = chat
+                        .preview()
+                        .unwrap_or("no messages yet")
+                        .chars()
+                        .take(preview_width.saturating_sub(indent.len()))
+                        .collect();
+
+                    vec![
+                        Line::from(spans),
+                        Line::styled(format!("{}{}", indent, preview), theme.muted),
+                    ]
+                }
+            };
 
             ListItem::new(lines).style(Style::default())
         })
         .collect();
 
+    let heights: Vec<u16> = chats.iter().map(|item| item.height() as u16).collect();
+
+    let title = match &app.tag_filter {
+        Some(tag) => format!("Chats [#{}] by {}", tag, app.sort_by.label()),
+        None => format!("Chats by {}", app.sort_by.label()),
+    };
+
     let chats = List::new(chats)
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .border_style(get_section_border_style(app, Section::Chats))
-                .title("Chats"),
-        )
-        .highlight_style(
-            Style::default()
-                .bg(Color::Yellow)
-                .fg(Color::Black)
-                .add_modifier(Modifier::BOLD),
+                .title(title),
         )
+        .highlight_style(app.theme.highlight)
         .highlight_symbol("* ");
 
-    f.render_stateful_widget(chats, area, &mut app.chats.state);
+    f.render_stateful_widget(chats, area, &mut app.sidebar.state);
+
+    app.areas.chats = area;
+    app.areas.chat_rows = list_rows(area, app.sidebar.state.offset(), &heights);
+}
+
+/// Search matches to highlight in a message
+struct SearchHighlight<'a> {
+    re: &'a Regex,
+    /// Index of the current match among the matches of the message
+    current: Option<usize>,
+    style: Style,
+    current_style: Style,
+}
+
+impl SearchHighlight<'_> {
+    /// Styled byte ranges of the matches in `line`, `seen` counts the matches
+    /// of the message before it
+    fn ranges(&self, line: &str, seen: &mut usize) -> Vec<(Range<usize>, Style)> {
+        self.re
+            .find_iter(line)
+            .map(|m| {
+                let style = match self.current == Some(*seen) {
+                    true => self.current_style,
+                    false => self.style,
+                };
+                *seen += 1;
+
+                (m.range(), style)
+            })
+            .collect()
+    }
+}
+
+/// Wraps message content to `width`, styling fenced code blocks
+fn message_lines(
+    content: &str,
+    width: usize,
+    text_style: Style,
+    code_style: Style,
+    search: Option<&SearchHighlight>,
+) -> Vec<Line<'static>> {
+    let mut lines: Vec<Line> = Vec::new();
+    let mut in_code = false;
+    // matches before the current line, to find the current one
+    let mut seen = 0;
+
+    for source_line in content.lines() {
+        let is_fence = source_line.trim_start().starts_with("```");
+        let style = if in_code || is_fence {
+            code_style
+        } else {
+            text_style
+        };
+
+        if is_fence {
+            in_code = !in_code;
+        }
+
+        let highlights = search.map_or_else(Vec::new, |s| s.ranges(source_line, &mut seen));
+
+        lines.extend(wrap_highlighted(source_line, width, style, &highlights));
+    }
+
+    lines
+}
+
+fn tool_result_lines(
+    chat: &Chat,
+    msg: &Message,
+    width: usize,
+    theme: &Theme,
+    search: Option<&SearchHighlight>,
+) -> Vec<Line<'static>> {
+    let name = msg
+        .tool_call_id
+        .as_deref()
+        .and_then(|id| chat.tool_call(id))
+        .map(|call| call.name.as_str())
+        .unwrap_or("tool");
+
+    let mut lines = vec![Line::styled(format!("↳ {}", name), theme.muted)];
+    let total = msg.content.lines().count();
+
+    let mut seen = 0;
+
+    for line in msg.shown_lines() {
+        // shifted past the indent
+        let highlights: Vec<_> = search
+            .map_or_else(Vec::new, |s| s.ranges(line, &mut seen))
+            .into_iter()
+            .map(|(range, style)| (range.start + 2..range.end + 2, style))
+            .collect();
+
+        lines.extend(wrap_highlighted(
+            &format!("  {}", line),
+            width,
+            theme.code_block,
+            &highlights,
+        ));
+    }
+
+    if total > TOOL_RESULT_LINES {
+        lines.push(Line::styled(
+            format!("  … {} more lines", total - TOOL_RESULT_LINES),
+            theme.muted,
+        ));
+    }
+
+    lines
 }
 
 fn render_messages(f: &mut Frame, app: &mut App, area: Rect) {
     let messages_style = get_section_border_style(app, Section::Messages);
+    let theme = app.theme.clone();
+    // borders, the scrollbar is drawn over the right one
+    let max_line_length = (area.width as usize).saturating_sub(2).max(10);
+    let search_re = app.search.regex();
+    let search_status = app.search.is_active().then(|| app.search.status());
+    let search_current = app.search.current_position();
 
-    match app.get_active_chat_mut() {
-        None => {
-            let list = List::new(Vec::<ListItem>::new()).block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .border_style(messages_style)
-                    .title("Messages (0)"),
-            );
+    app.areas.messages = area;
+    app.areas.message_rows.clear();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(messages_style);
+    let inner = block.inner(area);
+
+    let Some(chat) = app.get_active_chat_mut() else {
+        f.render_widget(block.title("Messages (0)"), area);
+        return;
+    };
+
+    let mut lines: Vec<Line> = Vec::new();
+    // line range of each message
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+
+    for (idx, msg) in chat.messages.items.iter().enumerate() {
+        let (alignment, text_style) = match msg.role {
+            Role::User => (Alignment::Right, theme.user_message),
+            Role::Assistant => (Alignment::Left, theme.assistant_message),
+            Role::Tool => (Alignment::Left, theme.muted),
+        };
 
-            f.render_widget(list, area);
+        let search = search_re.as_ref().map(|re| SearchHighlight {
+            re,
+            current: search_current
+                .filter(|&(message_idx, _)| message_idx == idx)
+                .map(|(_, n)| n),
+            style: theme.search_match,
+            current_style: theme.search_current,
+        });
+
+        let mut message = match msg.role {
+            Role::Tool => tool_result_lines(chat, msg, max_line_length, &theme, search.as_ref()),
+            _ => message_lines(
+                &msg.content,
+                max_line_length,
+                text_style,
+                theme.code_block,
+                search.as_ref(),
+            ),
+        };
+
+        // short single line messages are aligned by role
+        if let [line] = message.as_mut_slice() {
+            line.alignment = Some(alignment);
         }
-        Some(chat) => {
-            let messages: Vec<ListItem> = chat
-                .messages
-                .items
-                .iter()
-                .map(|msg| {
-                    // split content into lines of 30 characters
-                    let content = msg.content.clone();
-                    let alignment = match msg.role {
-                        Role::User => Alignment::Right,
-                        Role::Assistant => Alignment::Left,
-                    };
 
-                    let mut lines: Vec<Line> = Vec::new();
+        message.extend(msg.attachments.iter().map(|attachment| {
+            let icon = match attachment.kind {
+                AttachmentKind::File => "📎",
+                AttachmentKind::Command => "$",
+                AttachmentKind::Image => "🖼",
+            };
 
-                    let max_line_length = 100; // messages_chunks[0].width as usize - 50;
+            let size = attach::human_size(attachment.size());
+            let mut details = match &attachment.image {
+                Some(image) => format!("{}x{}, {}", image.width, image.height, size),
+                None => size,
+            };
 
-                    if content.len() <= max_line_length {
-                        let line = Line::raw(content).alignment(alignment);
-                        lines.push(line);
-                    } else {
-                        let mut content_as_lines = into_lines(content.clone(), max_line_length);
-                        lines.append(&mut content_as_lines);
-                    }
-
-                    lines.push(Line::raw(""));
-
-                    ListItem::new(lines).style(Style::default())
-                })
-                .collect();
-
-            let messages = List::new(messages)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .border_style(messages_style)
-                        .title(format!("Messages ({})", chat.messages.items.len())),
-                )
-                .highlight_style(
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::BOLD),
+            if attachment.changed {
+                details.push_str(", changed since sent");
+            }
+
+            Line::styled(
+                format!("{} {} ({})", icon, attachment.name, details),
+                theme.muted,
+            )
+            .alignment(alignment)
+        }));
+
+        message.extend(msg.tool_calls.iter().flat_map(|call| {
+            wrap_line(
+                &format!("⚙ {}({})", call.name, call.arguments),
+                max_line_length,
+            )
+            .into_iter()
+            .map(|l| Line::styled(l, theme.muted))
+        }));
+
+        message.push(Line::raw(""));
+
+        let start = lines.len();
+        lines.extend(message);
+        ranges.push((start, lines.len()));
+    }
+
+    let viewport_height = inner.height as usize;
+    chat.scroll.update(lines.len(), viewport_height);
+
+    if chat.scroll.reveal {
+        if let Some(&(start, end)) = chat.messages.state.selected().and_then(|i| ranges.get(i)) {
+            chat.scroll.show(start, end);
+        }
+
+        chat.scroll.reveal = false;
+    }
+
+    let offset = chat.scroll.offset;
+    let visible: Vec<Line> = lines
+        .into_iter()
+        .skip(offset)
+        .take(viewport_height)
+        .collect();
+
+    let mut title = format!("Messages ({})", chat.messages.items.len());
+
+    if let Some(status) = search_status {
+        title = format!("{} - {}", title, status);
+    }
+
+    if !chat.scroll.follow {
+        title = format!(
+            "{} - line {}/{}",
+            title,
+            offset + 1,
+            chat.scroll.content_height
+        );
+    }
+
+    f.render_widget(Paragraph::new(visible).block(block.title(title)), area);
+
+    // the part of each message on screen
+    let rows: Vec<(Rect, usize)> = ranges
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, &(start, end))| {
+            let top = start.max(offset);
+            let bottom = end.min(offset + viewport_height);
+
+            (top < bottom).then(|| {
+                let y = inner.y + (top - offset) as u16;
+                (
+                    Rect::new(inner.x, y, inner.width, (bottom - top) as u16),
+                    idx,
                 )
-                .highlight_spacing(HighlightSpacing::WhenSelected);
+            })
+        })
+        .collect();
 
-            f.render_stateful_widget(messages, area, &mut chat.messages.state);
+    if let Some(selected) = chat.messages.state.selected() {
+        for (row, _) in rows.iter().filter(|(_, idx)| *idx == selected) {
+            f.buffer_mut().set_style(*row, theme.message_highlight);
         }
-    };
+    }
+
+    if chat.scroll.content_height > viewport_height {
+        let mut scrollbar = ScrollbarState::new(chat.scroll.content_height - viewport_height)
+            .position(offset)
+            .viewport_content_length(viewport_height);
+
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .thumb_style(messages_style),
+            area.inner(&Margin {
+                horizontal: 0,
+                vertical: 1,
+            }),
+            &mut scrollbar,
+        );
+    }
+
+    app.areas.message_rows = rows;
+}
+
+/// A message full screen, with the key hints at the bottom
+fn render_viewer(f: &mut Frame, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(1)])
+        .split(f.size());
+
+    let theme = app.theme.clone();
+
+    if let Some(viewer) = &mut app.viewer {
+        let lines = viewer::markdown_lines(&viewer.content, &theme);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(theme.border_focused)
+            .title(format!(
+                "{} - wrap {}",
+                viewer.title,
+                if viewer.wrap { "on" } else { "off" }
+            ));
+        let inner = block.inner(chunks[0]);
+
+        let mut paragraph = Paragraph::new(lines);
+
+        if viewer.wrap {
+            paragraph = paragraph.wrap(Wrap { trim: false });
+        }
+
+        viewer
+            .scroll
+            .update(paragraph.line_count(inner.width), inner.height as usize);
+
+        let offset = viewer.scroll.offset;
+        let paragraph = paragraph
+            .scroll((offset as u16, viewer.column as u16))
+            .block(block);
+
+        f.render_widget(paragraph, chunks[0]);
+
+        if viewer.scroll.content_height > inner.height as usize {
+            let mut scrollbar =
+                ScrollbarState::new(viewer.scroll.content_height - inner.height as usize)
+                    .position(offset)
+                    .viewport_content_length(inner.height as usize);
+
+            f.render_stateful_widget(
+                Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                    .begin_symbol(None)
+                    .end_symbol(None)
+                    .thumb_style(theme.border_focused),
+                chunks[0].inner(&Margin {
+                    horizontal: 0,
+                    vertical: 1,
+                }),
+                &mut scrollbar,
+            );
+        }
+    }
+
+    render_help(f, app, chunks[1]);
 }
 
 fn render_chat_input(f: &mut Frame, app: &mut App, area: Rect) {
@@ -110,9 +458,15 @@ fn render_chat_input(f: &mut Frame, app: &mut App, area: Rect) {
         false => format!("Input ({}/{})", app.input.text.len(), app.input.max_length),
     };
 
-    let widget = Paragraph::new(app.input.text.to_string())
+    let mut text = Line::raw(app.input.text.to_string());
+
+    if let Some(hint) = slash::hint(&app.input.text) {
+        text.spans.push(Span::styled(hint, app.theme.muted));
+    }
+
+    let widget = Paragraph::new(text)
         .style(match app.focus {
-            Some(Section::Input) => Style::default().fg(Color::Green),
+            Some(Section::Input) => app.theme.input_focused,
             _ => Style::default(),
         })
         .wrap(Wrap { trim: true })
@@ -134,9 +488,9 @@ fn render_chat_input(f: &mut Frame, app: &mut App, area: Rect) {
     }
 }
 
-fn command<'a>(input: &'a str, description: &'a str, last: bool) -> Vec<Span<'a>> {
-    let command_style = Style::new().yellow().bold();
-    let description_style = Style::new().dim();
+fn command<'a>(theme: &Theme, input: &'a str, description: &'a str, last: bool) -> Vec<Span<'a>> {
+    let command_style = theme.help_key;
+    let description_style = theme.muted;
 
     let mut v = vec![
         Span::styled(input, command_style),
@@ -151,65 +505,45 @@ fn command<'a>(input: &'a str, description: &'a str, last: bool) -> Vec<Span<'a>
     v
 }
 
-type Command = (&'static str, &'static str);
-
-fn commands<'a>(commands: &[Command]) -> Vec<Span<'a>> {
+fn commands<'a>(theme: &Theme, commands: &'a [(String, String)]) -> Vec<Span<'a>> {
     let items: Vec<Span<'a>> = commands
         .iter()
         .enumerate()
-        .flat_map(|(idx, (cmd, desc))| command(cmd, desc, idx == commands.len() - 1))
+        .flat_map(|(idx, (cmd, desc))| command(theme, cmd, desc, idx == commands.len() - 1))
         .collect();
 
     items
 }
 
-fn render_help<'a>(f: &mut Frame, app: &mut App, area: Rect) {
-    let quit: Command = ("q", "quit");
-    let focus: Command = ("Enter", "focus");
-    let blur: Command = ("Esc", "blur");
-    let delete: Command = ("Backspace", "delete");
-    let vertical_movement: Command = ("H/J", "move");
-
-    let generic_help = commands(&[focus, quit]);
-
-    let mut text: Vec<Span<'a>> = match app.section {
-        Section::Messages => commands(&[
-            focus,
-            vertical_movement,
-            ("i", "input"),
-            ("c", "chats"),
-            quit,
-        ]),
-        Section::Input => commands(&[
-            focus,
-            vertical_movement,
-            ("m", "messages"),
-            ("c", "chats"),
-            quit,
-        ]),
-        _ => generic_help,
-    };
+/// Built from the active keymap, so rebinding a key updates the help
+fn render_help(f: &mut Frame, app: &mut App, area: Rect) {
+    let context = KeyContext::from_focus(app.focus);
+
+    let mut entries = app.keymap.help(context);
 
-    if let Some(focus) = &app.focus {
-        text = match focus {
-            Section::Chats => commands(&[
-                blur,
-                vertical_movement,
-                ("n", "new"),
-                ("N", "quick new"),
-                ("r", "rename"),
-                ("Enter", "open"),
-                delete,
-            ]),
-            Section::Messages => commands(&[blur, vertical_movement, delete]),
-            Section::Input | Section::Modal => commands(&[blur, ("Enter", "submit")]),
+    if context == KeyContext::Messages {
+        let search_labels = [Command::NextMatch.label(), Command::PrevMatch.label()];
+
+        match app.search.is_active() {
+            true => {
+                if let Some(esc) = entries.iter_mut().find(|(_, label)| label == "blur") {
+                    esc.1 = "clear find".to_string();
+                }
+            }
+            false => entries.retain(|(_, label)| !search_labels.contains(&label.as_str())),
         }
     }
 
-    let content: Line<'a> = text.into();
+    let mut text: Vec<Span> = commands(&app.theme, &entries);
+
+    if let Some(notice) = &app.notice {
+        text = vec![Span::styled(notice.clone(), app.theme.notice)];
+    }
+
+    let content: Line = text.into();
     let help = Paragraph::new(content)
         .alignment(Alignment::Center)
-        .block(Block::new().title(""));
+        .wrap(Wrap { trim: true });
 
     f.render_widget(help, area);
 }
@@ -217,69 +551,632 @@ fn render_help<'a>(f: &mut Frame, app: &mut App, area: Rect) {
 fn render_modal(f: &mut Frame, app: &mut App) {
     let area = f.size();
 
-    let width = area.width / 2;
-    let height = 3;
-
-    let popup_area = Rect {
-        x: area.width / 2 - width / 2,
-        y: area.height / 2 - 10,
-        width,
-        height,
-    };
+    let popup_area = layout::popup(area, (area.width / 2).max(40), 3);
 
     let title = match app.modal {
         Some(Modal::NewChat) => "New Chat",
         Some(Modal::RenameChat) => "Rename Chat",
+        Some(Modal::Search) => "Find in Chat",
+        Some(Modal::TagChat) => "Tags (comma separated)",
+        Some(Modal::MoveChat) => "Move to Folder (empty for none)",
+        Some(Modal::FilterByTag) => "Filter by Tag (empty for all)",
+        Some(Modal::Export(ExportScope::SelectedChat)) => "Export Chat to (.md, .json, .html)",
+        Some(Modal::Export(ExportScope::AllChats)) => "Export All Chats to (.md, .json, .html)",
+        Some(Modal::Import) => "Import ChatGPT export (conversations.json)",
+        Some(Modal::SwitchModel) => "Model",
         _ => "",
     };
 
+    let title = match app.modal {
+        Some(Modal::YankCodeBlock(count)) => format!("Copy Code Block (1-{})", count),
+        _ => title.to_string(),
+    };
+
     let max_chars_title = Line::from(vec![
-        app.modal_input.text.len().to_string().yellow(),
-        "/".dim(),
-        app.modal_input.max_length.to_string().dim(),
+        Span::styled(app.modal_input.text.len().to_string(), app.theme.help_key),
+        Span::styled("/", app.theme.muted),
+        Span::styled(app.modal_input.max_length.to_string(), app.theme.muted),
     ]);
 
     let popup = Popup::default()
         .content(app.modal_input.text.as_str())
         .title(title)
         .subtitle(max_chars_title)
-        .title_style(Style::new().white().bold())
-        .style(Style::new().yellow())
-        .border_style(Style::new().green().bold());
+        .title_style(app.theme.modal_title)
+        .style(app.theme.modal_text)
+        .border_style(app.theme.modal_border);
 
     f.render_widget(popup, popup_area);
 
+    // long paths scroll out of view, keep the cursor inside the box
+    let cursor = (app.modal_input.cursor_position() as u16).min(popup_area.width.saturating_sub(3));
+    f.set_cursor(popup_area.x + cursor + 1, popup_area.y + 1);
+}
+
+fn render_palette(f: &mut Frame, app: &mut App) {
+    let area = f.size();
+
+    let height = (app.palette.items.len() as u16 + 3)
+        .min(area.height.saturating_sub(4))
+        .max(3);
+    let popup_area = layout::popup(area, (area.width * 3 / 5).max(40), height);
+
+    f.render_widget(Clear, popup_area);
+
+    let block = Block::new()
+        .title("Commands")
+        .title_style(app.theme.modal_title)
+        .borders(Borders::ALL)
+        .border_style(app.theme.modal_border);
+
+    let inner = block.inner(popup_area);
+    f.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    let query = Line::from(vec![
+        Span::styled("> ", app.theme.modal_text),
+        Span::raw(app.modal_input.text.clone()),
+    ]);
+    f.render_widget(Paragraph::new(query), chunks[0]);
+
+    let label_width = inner.width.saturating_sub(14) as usize;
+
+    let items: Vec<ListItem> = app
+        .palette
+        .items
+        .iter()
+        .map(|command| {
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{:<width$}", command.title(), width = label_width)),
+                Span::styled(
+                    app.keymap
+                        .keys_for(&Action::Command(*command))
+                        .iter()
+                        .take(2)
+                        .map(|k| k.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    app.theme.muted,
+                ),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items).highlight_style(app.theme.highlight);
+
+    f.render_stateful_widget(list, chunks[1], &mut app.palette.state);
+
+    f.set_cursor(
+        chunks[0].x + 2 + app.modal_input.cursor_position() as u16,
+        chunks[0].y,
+    );
+}
+
+fn render_code_block_picker(f: &mut Frame, app: &mut App) {
+    let area = f.size();
+    let height = (app.code_blocks.items.len() as u16 + 2).min(area.height.saturating_sub(4));
+    let popup_area = layout::popup(area, (area.width * 3 / 5).max(40), height);
+
+    f.render_widget(Clear, popup_area);
+
+    let items: Vec<ListItem> = app
+        .code_blocks
+        .items
+        .iter()
+        .enumerate()
+        .map(|(i, block)| {
+            let first_line = block.code.lines().find(|l| !l.trim().is_empty());
+
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{:>2}. ", i + 1)),
+                Span::styled(
+                    format!("{:<10} ", block.language().unwrap_or("text")),
+                    app.theme.muted,
+                ),
+                Span::styled(
+                    format!("{} lines  ", block.code.lines().count()),
+                    app.theme.muted,
+                ),
+                Span::styled(
+                    first_line.unwrap_or("").trim().to_string(),
+                    app.theme.code_block,
+                ),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::new()
+                .title("Code Blocks")
+                .title_style(app.theme.modal_title)
+                .borders(Borders::ALL)
+                .border_style(app.theme.modal_border),
+        )
+        .highlight_style(app.theme.highlight);
+
+    f.render_stateful_widget(list, popup_area, &mut app.code_blocks.state);
+}
+
+/// The diff, or the whole content for a new file, before writing it
+fn render_confirm_save(f: &mut Frame, app: &mut App) {
+    let Some(pending) = &app.pending_save else {
+        return;
+    };
+
+    let area = f.size();
+    let popup_area = layout::popup(
+        area,
+        area.width.saturating_sub(8).max(40),
+        area.height.saturating_sub(4),
+    );
+
+    let (title, mut lines): (String, Vec<Line>) = match &pending.diff {
+        Some(diff) if diff.is_empty() => (
+            format!("{} is unchanged, write anyway?", pending.path.display()),
+            vec![],
+        ),
+        Some(diff) => (
+            format!("Overwrite {}?", pending.path.display()),
+            diff_lines(diff, &app.theme),
+        ),
+        None => (
+            format!(
+                "Create {} ({} lines)?",
+                pending.path.display(),
+                pending.content.lines().count()
+            ),
+            pending
+                .content
+                .lines()
+                .map(|l| Line::styled(l.to_string(), app.theme.diff_added))
+                .collect(),
+        ),
+    };
+
+    if let Err(e) = checked_path(&pending.path.to_string_lossy()) {
+        lines.insert(0, Line::styled(format!("Warning: {}", e), app.theme.notice));
+    }
+
+    let block = Block::new()
+        .title(title)
+        .title(
+            block::Title::from(" y/Enter write  n/Esc cancel ")
+                .position(block::Position::Bottom)
+                .alignment(Alignment::Right),
+        )
+        .title_style(app.theme.modal_title)
+        .borders(Borders::ALL)
+        .border_style(app.theme.modal_border);
+    let inner = block.inner(popup_area);
+
+    app.preview_scroll
+        .update(lines.len(), inner.height as usize);
+    let visible: Vec<Line> = lines
+        .into_iter()
+        .skip(app.preview_scroll.offset)
+        .take(inner.height as usize)
+        .collect();
+
+    f.render_widget(Clear, popup_area);
+    f.render_widget(Paragraph::new(visible).block(block), popup_area);
+}
+
+/// The tool call waiting for approval, with its arguments
+fn render_approve_tool(f: &mut Frame, app: &mut App) {
+    let Some(call) = app.tool_calls.as_ref().and_then(|p| p.calls.front()) else {
+        return;
+    };
+
+    let remaining = app.tool_calls.as_ref().map_or(0, |p| p.calls.len());
+
+    let area = f.size();
+    let arguments = serde_json::from_str::<serde_json::Value>(&call.arguments)
+        .and_then(|args| serde_json::to_string_pretty(&args))
+        .unwrap_or_else(|_| call.arguments.clone());
+
+    let mut lines: Vec<Line> = arguments
+        .lines()
+        .map(|l| Line::styled(l.to_string(), app.theme.code_block))
+        .collect();
+
+    // commands are confirmed by typing, see `render_confirm_run`
+    let typed = call.name == "run_command";
+    let running = app.tool_calls.as_ref().is_some_and(|p| p.running);
+
+    if running {
+        lines.push(Line::raw(""));
+        lines.push(Line::styled("Running…", app.theme.muted));
+    } else if typed {
+        lines.push(Line::raw(""));
+        lines.push(Line::from(vec![
+            Span::styled("> ", app.theme.modal_text),
+            Span::raw(app.modal_input.text.clone()),
+        ]));
+    }
+
+    let popup_area = layout::popup(
+        area,
+        area.width.saturating_sub(8).max(40),
+        (lines.len() as u16 + 2).min(area.height.saturating_sub(4)),
+    );
+
+    let mut title = format!("Allow the model to call {}?", call.name);
+    if remaining > 1 {
+        title = format!("{} (1 of {})", title, remaining);
+    }
+
+    let keys = match (running, typed) {
+        (true, _) => " Esc deny all ",
+        (false, true) => " type run to allow, n to deny  Esc deny all ",
+        (false, false) => " y/Enter allow  n deny  Esc deny all ",
+    };
+
+    let block = Block::new()
+        .title(title)
+        .title(
+            block::Title::from(keys)
+                .position(block::Position::Bottom)
+                .alignment(Alignment::Right),
+        )
+        .title_style(app.theme.modal_title)
+        .borders(Borders::ALL)
+        .border_style(app.theme.modal_border);
+    let inner = block.inner(popup_area);
+
+    app.preview_scroll
+        .update(lines.len(), inner.height as usize);
+    let visible: Vec<Line> = lines
+        .into_iter()
+        .skip(app.preview_scroll.offset)
+        .take(inner.height as usize)
+        .collect();
+
+    f.render_widget(Clear, popup_area);
+    f.render_widget(Paragraph::new(visible).block(block), popup_area);
+
+    if typed && !running {
+        f.set_cursor(
+            inner.x + 2 + app.modal_input.cursor_position() as u16,
+            inner.y + inner.height.saturating_sub(1),
+        );
+    }
+}
+
+/// A `!command` before it runs, then the message with its output before sending it
+fn render_confirm_command(f: &mut Frame, app: &mut App) {
+    let Some(message) = &app.pending_message else {
+        return;
+    };
+
+    let output = message
+        .attachments
+        .iter()
+        .find(|a| a.kind == AttachmentKind::Command);
+
+    let area = f.size();
+    let popup_area = layout::popup(
+        area,
+        area.width.saturating_sub(8).max(40),
+        area.height.saturating_sub(4),
+    );
+
+    let mut lines = vec![
+        Line::styled(message.content.clone(), app.theme.user_message),
+        Line::raw(""),
+    ];
+
+    let (title, keys) = match output {
+        Some(output) => {
+            lines.extend(
+                output
+                    .content
+                    .lines()
+                    .map(|l| Line::styled(l.to_string(), app.theme.code_block)),
+            );
+
+            (
+                format!(
+                    "Send with the output of `{}` ({})?",
+                    output.name,
+                    attach::human_size(output.content.len())
+                ),
+                " y/Enter send  n/Esc edit ",
+            )
+        }
+        None if app.command_running => {
+            lines.push(Line::styled("Running…", app.theme.muted));
+            ("Running the command".to_string(), " n/Esc edit ")
+        }
+        None => (
+            "Run this command and preview its output?".to_string(),
+            " y/Enter run  n/Esc edit ",
+        ),
+    };
+
+    let block = Block::new()
+        .title(title)
+        .title(
+            block::Title::from(keys)
+                .position(block::Position::Bottom)
+                .alignment(Alignment::Right),
+        )
+        .title_style(app.theme.modal_title)
+        .borders(Borders::ALL)
+        .border_style(app.theme.modal_border);
+    let inner = block.inner(popup_area);
+
+    app.preview_scroll
+        .update(lines.len(), inner.height as usize);
+    let visible: Vec<Line> = lines
+        .into_iter()
+        .skip(app.preview_scroll.offset)
+        .take(inner.height as usize)
+        .collect();
+
+    f.render_widget(Clear, popup_area);
+    f.render_widget(Paragraph::new(visible).block(block), popup_area);
+}
+
+/// The command about to run, confirmed by typing `run` or `send`
+fn render_confirm_run(f: &mut Frame, app: &mut App) {
+    let Some(pending) = &app.pending_run else {
+        return;
+    };
+
+    let area = f.size();
+    let script: Vec<&str> = pending.script.lines().collect();
+    let popup_area = layout::popup(
+        area,
+        area.width.saturating_sub(8).max(40),
+        (script.len() as u16 + 4).min(area.height.saturating_sub(4)),
+    );
+
+    let cwd = std::env::current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_default();
+
+    let block = Block::new()
+        .title(format!("Run in {}?", cwd))
+        .title(
+            block::Title::from(" type run, or send to share the output  Esc cancel ")
+                .position(block::Position::Bottom)
+                .alignment(Alignment::Right),
+        )
+        .title_style(app.theme.modal_title)
+        .borders(Borders::ALL)
+        .border_style(app.theme.modal_border);
+    let inner = block.inner(popup_area);
+
+    // the prompt stays visible, long scripts are cut
+    let visible = (inner.height as usize).saturating_sub(2);
+    let mut lines: Vec<Line> = script
+        .iter()
+        .take(visible)
+        .map(|l| Line::styled(l.to_string(), app.theme.code_block))
+        .collect();
+
+    if script.len() > visible {
+        lines.pop();
+        lines.push(Line::styled(
+            format!("… {} more lines", script.len() - visible + 1),
+            app.theme.muted,
+        ));
+    }
+
+    lines.push(Line::raw(""));
+    lines.push(Line::from(vec![
+        Span::styled("> ", app.theme.modal_text),
+        Span::raw(app.modal_input.text.clone()),
+    ]));
+
+    f.render_widget(Clear, popup_area);
+    f.render_widget(Paragraph::new(lines).block(block), popup_area);
+
     f.set_cursor(
-        popup_area.x + app.modal_input.cursor_position() as u16 + 1,
-        popup_area.y + 1,
+        inner.x + 2 + app.modal_input.cursor_position() as u16,
+        inner.y + inner.height.saturating_sub(1),
+    );
+}
+
+/// Every hunk of `App::patch_review`, rejected ones dimmed
+fn render_apply_patch(f: &mut Frame, app: &mut App) {
+    let Some(review) = &app.patch_review else {
+        return;
+    };
+
+    let area = f.size();
+    let popup_area = layout::popup(
+        area,
+        area.width.saturating_sub(8).max(40),
+        area.height.saturating_sub(4),
+    );
+
+    let mut lines: Vec<Line> = Vec::new();
+    let mut selected = (0, 0);
+    let mut number = 0;
+
+    for file in &review.files {
+        let status = match (&file.old_path, &file.new_path) {
+            (None, _) => " (new file)",
+            (_, None) => " (deleted)",
+            _ => "",
+        };
+        lines.push(Line::styled(
+            format!("{}{}", file.path(), status),
+            app.theme.heading,
+        ));
+
+        for hunk in &file.hunks {
+            let start = lines.len();
+            let marker = match hunk.accepted {
+                true => "[x]",
+                false => "[ ]",
+            };
+            let header_style = match number == review.selected {
+                true => app.theme.highlight,
+                false => app.theme.muted,
+            };
+            lines.push(Line::styled(
+                format!("{} {}", marker, hunk.header),
+                header_style,
+            ));
+
+            for line in &hunk.lines {
+                let (prefix, text, style) = match line {
+                    HunkLine::Context(l) => (' ', l, Style::default()),
+                    HunkLine::Remove(l) => ('-', l, app.theme.diff_removed),
+                    HunkLine::Add(l) => ('+', l, app.theme.diff_added),
+                };
+                let style = match hunk.accepted {
+                    true => style,
+                    false => app.theme.muted,
+                };
+                lines.push(Line::styled(format!("{}{}", prefix, text), style));
+            }
+
+            if number == review.selected {
+                selected = (start, lines.len());
+            }
+            number += 1;
+        }
+    }
+
+    let accepted = review
+        .files
+        .iter()
+        .flat_map(|f| &f.hunks)
+        .filter(|h| h.accepted)
+        .count();
+
+    let block = Block::new()
+        .title(format!(
+            "Apply diff: {}/{} hunks in {} file(s)",
+            accepted,
+            review.hunk_count(),
+            review.files.len()
+        ))
+        .title(
+            block::Title::from(" space toggle  y/n accept/reject  a/Enter apply  Esc cancel ")
+                .position(block::Position::Bottom)
+                .alignment(Alignment::Right),
+        )
+        .title_style(app.theme.modal_title)
+        .borders(Borders::ALL)
+        .border_style(app.theme.modal_border);
+    let inner = block.inner(popup_area);
+
+    app.preview_scroll
+        .update(lines.len(), inner.height as usize);
+    app.preview_scroll.show(selected.0, selected.1);
+    let visible: Vec<Line> = lines
+        .into_iter()
+        .skip(app.preview_scroll.offset)
+        .take(inner.height as usize)
+        .collect();
+
+    f.render_widget(Clear, popup_area);
+    f.render_widget(Paragraph::new(visible).block(block), popup_area);
+}
+
+/// Colors a unified diff
+fn diff_lines(diff: &str, theme: &Theme) -> Vec<Line<'static>> {
+    diff.lines()
+        .map(|line| {
+            let style = match line {
+                _ if line.starts_with("+++") || line.starts_with("---") => theme.heading,
+                _ if line.starts_with("@@") => theme.muted,
+                _ if line.starts_with('+') => theme.diff_added,
+                _ if line.starts_with('-') => theme.diff_removed,
+                _ => Style::default(),
+            };
+
+            Line::styled(line.to_string(), style)
+        })
+        .collect()
+}
+
+/// Shown instead of the UI when nothing useful fits
+fn render_too_small(f: &mut Frame, app: &App) {
+    let area = f.size();
+    let text = vec![
+        Line::styled("Terminal too small", app.theme.notice),
+        Line::styled(
+            format!(
+                "{}x{}, need {}x{}",
+                area.width,
+                area.height,
+                layout::MIN_WIDTH,
+                layout::MIN_HEIGHT
+            ),
+            app.theme.muted,
+        ),
+    ];
+
+    let top = area.height.saturating_sub(2) / 2;
+    let area = Rect {
+        y: area.y + top,
+        height: area.height - top,
+        ..area
+    };
+
+    f.render_widget(
+        Paragraph::new(text)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        area,
     );
 }
 
 pub fn render(f: &mut Frame, app: &mut App) {
-    let main_layout = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(25), Constraint::Percentage(75)])
-        .split(f.size());
+    if layout::too_small(f.size()) {
+        app.areas = Areas::default();
+        render_too_small(f, app);
+        return;
+    }
 
-    let messages_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            // help
-            Constraint::Percentage(5),
-            // messages
-            Constraint::Percentage(88),
-            // input
-            Constraint::Percentage(7),
-        ])
-        .split(main_layout[1]);
-
-    render_chats(f, app, main_layout[0]);
-
-    render_help(f, app, messages_chunks[0]);
-    render_messages(f, app, messages_chunks[1]);
-    render_chat_input(f, app, messages_chunks[2]);
-
-    if app.modal.is_some() {
-        render_modal(f, app);
+    if app.viewer.is_some() {
+        render_viewer(f, app);
+
+        render_modals(f, app);
+        return;
+    }
+
+    let panes = layout::panes(f.size(), &app.layout, app.section);
+    app.areas = Areas::default();
+
+    if let Some(area) = panes.chats {
+        render_chats(f, app, area);
+    }
+
+    render_help(f, app, panes.help);
+
+    if let Some(area) = panes.messages {
+        render_messages(f, app, area);
+    }
+
+    if let Some(area) = panes.input {
+        render_chat_input(f, app, area);
+        app.areas.input = area;
+    }
+
+    render_modals(f, app);
+}
+
+fn render_modals(f: &mut Frame, app: &mut App) {
+    match app.modal {
+        Some(Modal::Palette) => render_palette(f, app),
+        Some(Modal::PickCodeBlock(_)) => render_code_block_picker(f, app),
+        Some(Modal::ConfirmSave) => render_confirm_save(f, app),
+        Some(Modal::ApplyPatch) => render_apply_patch(f, app),
+        Some(Modal::ConfirmRun) => render_confirm_run(f, app),
+        Some(Modal::ConfirmCommand) => render_confirm_command(f, app),
+        Some(Modal::ApproveTool) => render_approve_tool(f, app),
+        Some(_) => render_modal(f, app),
+        None => {}
     }
 }
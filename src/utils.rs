@@ -1,6 +1,9 @@
 use crate::state::{App, Section};
+use crate::theme::Theme;
 use color_eyre::eyre::Result;
 use ratatui::prelude::*;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
 
 pub fn setup_panic_handler() -> Result<()> {
     let (panic_hook, eyre_hook) = color_eyre::config::HookBuilder::default()
@@ -58,54 +61,215 @@ enum SectionStatus {
     Normal,
 }
 
-fn get_border_style(status: SectionStatus) -> Style {
+fn get_border_style(theme: &Theme, status: SectionStatus) -> Style {
     match status {
-        SectionStatus::Hovered => Style::default().fg(Color::Yellow),
-        SectionStatus::Focused => Style::default()
-            .fg(Color::Green)
-            .add_modifier(Modifier::BOLD),
-        SectionStatus::Normal => Style::default(),
+        SectionStatus::Hovered => theme.border_hovered,
+        SectionStatus::Focused => theme.border_focused,
+        SectionStatus::Normal => theme.border,
     }
 }
 
 pub fn get_section_border_style(app: &mut App, section: Section) -> Style {
+    let theme = &app.theme;
+
     match &app.focus {
-        Some(selected) if selected == &section => get_border_style(SectionStatus::Focused),
+        Some(selected) if selected == &section => get_border_style(theme, SectionStatus::Focused),
         _ => match &app.section {
-            s if s == &section => get_border_style(SectionStatus::Hovered),
-            _ => get_border_style(SectionStatus::Normal),
+            s if s == &section => get_border_style(theme, SectionStatus::Hovered),
+            _ => get_border_style(theme, SectionStatus::Normal),
         },
     }
 }
 
 // String Utils
 
+/// Subsequence match of `pattern` in `text`, higher is better.
+/// Consecutive characters and word starts score extra.
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut last_match: Option<usize> = None;
+
+    for p in pattern
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+    {
+        let offset = text[position..].iter().position(|c| *c == p)?;
+        let idx = position + offset;
+
+        score += 1;
+
+        if last_match.is_some_and(|last| last + 1 == idx) {
+            score += 5;
+        }
+
+        if idx == 0 || text[idx - 1] == ' ' {
+            score += 3;
+        }
+
+        score -= offset as i64;
+        last_match = Some(idx);
+        position = idx + 1;
+    }
+
+    Some(score)
+}
+
 pub fn trim_spaces(s: &str) -> String {
     let re = regex::Regex::new(r"^\s+|\s+$").unwrap();
 
     re.replace_all(s, "").to_string()
 }
 
-pub fn into_lines(str: String, max_length: usize) -> Vec<Line<'static>> {
-    let mut lines: Vec<Line> = Vec::new();
+/// Greedy word wrap, words longer than `max_length` are split
+pub fn wrap_line(line: &str, max_length: usize) -> Vec<String> {
+    let max_length = max_length.max(1);
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut current_length = 0;
+
+    for word in line.split(' ') {
+        let mut word: Vec<char> = word.chars().collect();
+
+        while word.len() > max_length {
+            if current_length > 0 {
+                lines.push(std::mem::take(&mut current));
+                current_length = 0;
+            }
 
-    let mut current_line_length = 0;
-    let mut content = String::new();
-    let words = str.split(' ');
+            lines.push(word.drain(..max_length).collect());
+        }
 
-    for word in words {
-        let word_length = word.len();
+        let separator = usize::from(current_length > 0);
 
-        if current_line_length + word_length > max_length {
-            lines.push(Line::raw(trim_spaces(&content.clone())));
-            content.clear();
-            current_line_length = 0;
-        } else {
-            content.push_str(word);
-            content.push(' ');
-            current_line_length += word_length;
+        if current_length + separator + word.len() > max_length {
+            lines.push(std::mem::take(&mut current));
+            current_length = 0;
+        } else if separator == 1 {
+            current.push(' ');
+            current_length += 1;
         }
+
+        current.extend(word.iter());
+        current_length += word.len();
     }
 
+    lines.push(current);
     lines
 }
+
+/// Wraps `line` like `wrap_line`, styling the byte ranges of `highlights`,
+/// also where they continue on the next row
+pub fn wrap_highlighted(
+    line: &str,
+    max_length: usize,
+    base: Style,
+    highlights: &[(Range<usize>, Style)],
+) -> Vec<Line<'static>> {
+    let mut cursor = 0;
+
+    wrap_line(line, max_length)
+        .into_iter()
+        .map(|row| {
+            // rows are pieces of `line` in order, minus the spaces they were split at
+            let start = line[cursor..]
+                .find(row.as_str())
+                .map_or(cursor, |i| cursor + i);
+            let end = start + row.len();
+            cursor = end;
+
+            let mut spans: Vec<Span<'static>> = Vec::new();
+            let mut last = start;
+
+            for (range, style) in highlights
+                .iter()
+                .filter(|(range, _)| range.start < end && range.end > start)
+            {
+                let from = range.start.max(start);
+                let to = range.end.min(end);
+
+                if from > last {
+                    spans.push(Span::styled(line[last..from].to_string(), base));
+                }
+
+                spans.push(Span::styled(line[from..to].to_string(), base.patch(*style)));
+                last = to;
+            }
+
+            if last < end || spans.is_empty() {
+                spans.push(Span::styled(line[last..end].to_string(), base));
+            }
+
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Only relative paths below the current directory pass, for paths that come
+/// from the model. Symlinks are followed, a link to `/` inside the project
+/// does not make its target reachable.
+pub fn checked_path(path: &str) -> anyhow::Result<PathBuf> {
+    let path = Path::new(path);
+    let outside = || anyhow::anyhow!("{} is outside the current directory", path.display());
+
+    let escapes = path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+
+    if path.as_os_str().is_empty() || escapes {
+        return Err(outside());
+    }
+
+    // files about to be written don't exist yet, resolve what does
+    let existing = path
+        .ancestors()
+        .find(|p| p.as_os_str().is_empty() || p.symlink_metadata().is_ok())
+        .unwrap_or(path);
+    let existing = match existing.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => existing,
+    };
+
+    let root = std::env::current_dir()?.canonicalize()?;
+    let resolved = existing
+        .canonicalize()
+        .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+
+    if !resolved.starts_with(root) {
+        return Err(outside());
+    }
+
+    Ok(path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_must_stay_in_the_current_directory() {
+        assert!(checked_path("src/main.rs").is_ok());
+        assert!(checked_path("./src/not-yet/written.rs").is_ok());
+
+        assert!(checked_path("").is_err());
+        assert!(checked_path("/etc/passwd").is_err());
+        assert!(checked_path("src/../../x").is_err());
+    }
+
+    #[test]
+    fn symlinks_are_followed() {
+        let dir = Path::new("target/checked-path-test");
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir).unwrap();
+        std::os::unix::fs::symlink("/", dir.join("root")).unwrap();
+        std::os::unix::fs::symlink("../../src", dir.join("src")).unwrap();
+
+        assert!(checked_path("target/checked-path-test/root/etc/passwd").is_err());
+        assert!(checked_path("target/checked-path-test/root").is_err());
+        assert!(checked_path("target/checked-path-test/src/main.rs").is_ok());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use ratatui::prelude::*;
+
+use crate::components::scroll::Scroll;
+use crate::theme::Theme;
+
+/// A single message shown full screen
+#[derive(Debug, Clone)]
+pub struct Viewer {
+    pub title: String,
+    pub content: String,
+    pub wrap: bool,
+    pub scroll: Scroll,
+    /// Columns scrolled to the right, only without wrapping
+    pub column: usize,
+}
+
+impl Viewer {
+    pub fn new(title: String, content: String) -> Self {
+        Self {
+            title,
+            content,
+            wrap: true,
+            scroll: Scroll {
+                follow: false,
+                ..Scroll::default()
+            },
+            column: 0,
+        }
+    }
+
+    pub fn toggle_wrap(&mut self) {
+        self.wrap = !self.wrap;
+        self.column = 0;
+    }
+
+    pub fn left(&mut self) {
+        self.column = self.column.saturating_sub(4);
+    }
+
+    pub fn right(&mut self) {
+        if !self.wrap {
+            self.column += 4;
+        }
+    }
+}
+
+/// Light markdown rendering: headings, lists, quotes, `inline code`, **bold**
+/// and numbered code blocks
+pub fn markdown_lines(content: &str, theme: &Theme) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    let mut code_line: Option<usize> = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("```") {
+            code_line = match code_line {
+                Some(_) => None,
+                None => Some(1),
+            };
+            lines.push(Line::styled(line.to_string(), theme.muted));
+            continue;
+        }
+
+        if let Some(number) = code_line.as_mut() {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{:>4} │ ", number), theme.muted),
+                Span::styled(line.to_string(), theme.code_block),
+            ]));
+            *number += 1;
+            continue;
+        }
+
+        let heading = trimmed.trim_start_matches('#');
+
+        if trimmed.starts_with('#') && heading.starts_with(' ') {
+            lines.push(Line::styled(heading.trim().to_string(), theme.heading));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            lines.push(Line::styled(
+                format!("│ {}", quote.trim_start()),
+                theme.muted.italic(),
+            ));
+        } else if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            let indent = &line[..line.len() - trimmed.len()];
+            let mut spans = vec![Span::raw(format!("{}• ", indent))];
+            spans.extend(inline_spans(item, theme));
+            lines.push(Line::from(spans));
+        } else {
+            lines.push(Line::from(inline_spans(line, theme)));
+        }
+    }
+
+    lines
+}
+
+fn inline_spans(text: &str, theme: &Theme) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let next = [("`", theme.code_block), ("**", Style::new().bold())]
+            .into_iter()
+            .filter_map(|(marker, style)| {
+                let start = rest.find(marker)?;
+                let end = rest[start + marker.len()..].find(marker)? + start + marker.len();
+                Some((start, end, marker, style))
+            })
+            .min_by_key(|(start, ..)| *start);
+
+        let Some((start, end, marker, style)) = next else {
+            spans.push(Span::raw(rest.to_string()));
+            break;
+        };
+
+        if start > 0 {
+            spans.push(Span::raw(rest[..start].to_string()));
+        }
+
+        spans.push(Span::styled(
+            rest[start + marker.len()..end].to_string(),
+            style,
+        ));
+        rest = &rest[end + marker.len()..];
+    }
+
+    spans
+}
+
+/// Pipes `text` into `$PAGER` (`less -R` by default) and waits for it to exit.
+/// The caller is responsible for giving it the terminal.
+pub fn page(text: &str) -> anyhow::Result<()> {
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| "less -R".to_string());
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&pager)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("could not run `{}`: {}", pager, e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // the pager may quit before reading everything
+        let _ = stdin.write_all(text.as_bytes());
+    }
+
+    let status = child.wait()?;
+
+    if !status.success() {
+        anyhow::bail!("`{}` exited with {}", pager, status);
+    }
+
+    Ok(())
+}